@@ -0,0 +1,190 @@
+//! Sizes the number of ticks the template and prediction worlds can execute each frame.
+//!
+//! By default the [`PredictionRates`](crate::client::PredictionRates) give the template and prediction worlds
+//! a fixed number of ticks for every tick of the main world.
+//! Rates that are too high cause hitching when prediction has to catch up, and rates that are too low make it fall behind.
+//!
+//! Insert the [`AdaptivePredictionBudget`] resource to instead measure how long a tick of each world takes,
+//! and give them as many ticks as fit within [`AdaptivePredictionBudget::frame_slice`] every frame.
+//! The template world is given ticks first, because prediction starts from it.
+//!
+//! Either way, a [`PredictionFallingBehind`] message is written for every frame where the template world can't reach
+//! the latest tick from the server, or the prediction world can't execute as many ticks as the main world advanced by.
+
+use std::time::Duration;
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel, system::SystemParam},
+    prelude::*,
+};
+
+use crate::{
+    client::{
+        ClientSimulationSystems, PredictionBudget, drive_simulation_time,
+        spectator::Spectator,
+        template_world::{ServerTickSamples, TemplateWorld},
+    },
+    common::simulation::{SimulationTick, SimulationTime, SimulationTimeExt},
+};
+
+pub(crate) fn build(app: &mut App, schedule: Interned<dyn ScheduleLabel>) {
+    app.add_message::<PredictionFallingBehind>();
+    app.init_resource::<SimulationTickCosts>();
+
+    app.add_systems(
+        schedule,
+        size_prediction_budget
+            .in_set(ClientSimulationSystems::ReceiveUpdates)
+            .after(drive_simulation_time),
+    );
+}
+
+/// Insert this resource to size the prediction budget by how long ticks take to execute.
+///
+/// See the [`budget`](self) module.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct AdaptivePredictionBudget {
+    /// How much time the template and prediction worlds can spend executing ticks each frame.
+    pub frame_slice: Duration,
+}
+
+impl Default for AdaptivePredictionBudget {
+    fn default() -> Self {
+        AdaptivePredictionBudget {
+            frame_slice: Duration::from_millis(4),
+        }
+    }
+}
+
+/// Written when the template or prediction world can't execute all the ticks it needs to this frame.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct PredictionFallingBehind {
+    /// How many ticks from the server the template world won't execute this frame.
+    pub template_ticks: u32,
+    /// How many fewer ticks the prediction world can execute this frame than the main world advanced by.
+    pub prediction_ticks: u32,
+}
+
+/// The average time it takes the template and prediction worlds to execute a tick on the main thread.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct SimulationTickCosts {
+    template: Option<Duration>,
+    prediction: Option<Duration>,
+}
+
+impl SimulationTickCosts {
+    /// How much a new measurement changes the average.
+    const SMOOTHING: f64 = 0.125;
+
+    pub fn template(&self) -> Option<Duration> {
+        self.template
+    }
+
+    pub fn prediction(&self) -> Option<Duration> {
+        self.prediction
+    }
+
+    pub(crate) fn record_template(&mut self, elapsed: Duration, ticks: u32) {
+        Self::record(&mut self.template, elapsed, ticks);
+    }
+
+    pub(crate) fn record_prediction(&mut self, elapsed: Duration, ticks: u32) {
+        Self::record(&mut self.prediction, elapsed, ticks);
+    }
+
+    fn record(average: &mut Option<Duration>, elapsed: Duration, ticks: u32) {
+        if ticks == 0 {
+            return;
+        }
+
+        let sample = elapsed / ticks;
+
+        *average = Some(match *average {
+            None => sample,
+            Some(average) => {
+                average.mul_f64(1. - Self::SMOOTHING) + sample.mul_f64(Self::SMOOTHING)
+            }
+        });
+    }
+}
+
+/// How many ticks fit within a slice of time.
+///
+/// At least one tick is allowed so that the worlds keep advancing, and so that an unmeasured world gets measured.
+fn ticks_within(slice: Duration, cost: Option<Duration>) -> u32 {
+    let Some(cost) = cost.filter(|cost| !cost.is_zero()) else {
+        return 1;
+    };
+
+    ((slice.as_secs_f64() / cost.as_secs_f64()) as u32).max(1)
+}
+
+/// What the prediction budget is sized from.
+#[derive(SystemParam)]
+struct BudgetInputs<'w> {
+    adaptive: Option<Res<'w, AdaptivePredictionBudget>>,
+    spectator: Option<Res<'w, Spectator>>,
+    costs: Res<'w, SimulationTickCosts>,
+    server_time: Res<'w, ServerTickSamples>,
+    template_world: Res<'w, TemplateWorld>,
+    time: Res<'w, Time<SimulationTime>>,
+}
+
+fn size_prediction_budget(
+    inputs: BudgetInputs,
+    mut budget: ResMut<PredictionBudget>,
+    mut falling_behind: MessageWriter<PredictionFallingBehind>,
+    mut last_target_tick: Local<Option<SimulationTick>>,
+) {
+    let BudgetInputs {
+        adaptive,
+        spectator,
+        costs,
+        server_time,
+        template_world,
+        time,
+    } = inputs;
+
+    let template_tick = template_world
+        .resource::<Time<SimulationTime>>()
+        .current_tick();
+
+    let mut desired_template_tick = server_time.latest();
+
+    if spectator.is_some() {
+        desired_template_tick = desired_template_tick.min(time.target_tick());
+    }
+
+    let template_ticks = desired_template_tick.saturating_sub(*template_tick);
+
+    if let Some(adaptive) = adaptive {
+        budget.template = ticks_within(adaptive.frame_slice, costs.template).min(template_ticks);
+
+        let template_time = costs.template.unwrap_or_default() * budget.template;
+        let remaining = adaptive.frame_slice.saturating_sub(template_time);
+
+        budget.prediction = ticks_within(remaining, costs.prediction);
+    }
+
+    // Prediction can only keep up if it executes at least as many ticks as the main world advanced by.
+    // Spectators don't run the prediction world.
+    let main_ticks = last_target_tick.map_or(0, |last_target_tick| {
+        time.target_tick().saturating_sub(*last_target_tick)
+    });
+    *last_target_tick = Some(time.target_tick());
+
+    let prediction_ticks = match spectator {
+        Some(_) => 0,
+        None => main_ticks,
+    };
+
+    let template_deficit = template_ticks.saturating_sub(budget.template);
+    let prediction_deficit = prediction_ticks.saturating_sub(budget.prediction);
+
+    if template_deficit > 0 || prediction_deficit > 0 {
+        falling_behind.write(PredictionFallingBehind {
+            template_ticks: template_deficit,
+            prediction_ticks: prediction_deficit,
+        });
+    }
+}
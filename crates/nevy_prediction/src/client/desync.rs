@@ -0,0 +1,134 @@
+//! Compares the server's simulation checksums against the [`TemplateWorld`].
+//!
+//! See the [`checksum`](crate::common::simulation::checksum) module.
+
+use std::collections::VecDeque;
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    prelude::*,
+};
+use tracing::{error, warn};
+
+use crate::{
+    client::{ClientSimulationSystems, PredictionServerConnection, template_world::TemplateWorld},
+    common::{
+        ServerSimulationChecksum,
+        loopback::PredictionReceiver,
+        simulation::{
+            SimulationTick, SimulationTime, SimulationTimeExt,
+            checksum::{ChecksumRegistry, SimulationChecksums, TickChecksums},
+        },
+    },
+};
+
+pub(crate) fn build(app: &mut App, schedule: Interned<dyn ScheduleLabel>) {
+    app.add_message::<SimulationDesync>();
+    app.init_resource::<PendingServerChecksums>();
+
+    app.add_systems(
+        schedule,
+        (
+            receive_server_checksums.in_set(ClientSimulationSystems::ReceiveUpdates),
+            compare_checksums
+                .after(ClientSimulationSystems::RunTemplateWorld)
+                .before(ClientSimulationSystems::QueuePredictionUpdates),
+        ),
+    );
+}
+
+/// Written when the checksums of the [`TemplateWorld`] don't match the server's for a tick.
+#[derive(Message, Clone, Debug)]
+pub struct SimulationDesync {
+    /// The tick that the checksums were computed after.
+    pub tick: SimulationTick,
+    /// The first registered type that didn't match.
+    ///
+    /// Is `None` if the client and server have a different number of types registered for checksums.
+    pub type_name: Option<&'static str>,
+}
+
+/// Server checksums that the template world hasn't reached yet.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub(crate) struct PendingServerChecksums(VecDeque<TickChecksums>);
+
+fn receive_server_checksums(
+    mut received: PredictionReceiver<ServerSimulationChecksum, PredictionServerConnection>,
+    mut pending: ResMut<PendingServerChecksums>,
+) {
+    for (connection_entity, is_server, ServerSimulationChecksum(checksums)) in received.drain() {
+        if !is_server {
+            warn!(
+                "Received a prediction message from a connection that isn't the server: {}",
+                connection_entity
+            );
+
+            continue;
+        }
+
+        pending.push_back(checksums);
+    }
+}
+
+pub(crate) fn compare_checksums(
+    template_world: Res<TemplateWorld>,
+    mut pending: ResMut<PendingServerChecksums>,
+    mut desyncs: MessageWriter<SimulationDesync>,
+) {
+    // `current_tick` is the next tick to execute, so checksums exist for the ticks before it.
+    let template_tick = template_world
+        .resource::<Time<SimulationTime>>()
+        .current_tick();
+
+    let registry = template_world.resource::<ChecksumRegistry>();
+    let template_checksums = template_world.resource::<SimulationChecksums>();
+
+    while let Some(server_checksums) = pending.front() {
+        if server_checksums.tick >= template_tick {
+            break;
+        }
+
+        let server_checksums = pending.pop_front().unwrap();
+
+        let Some(local_checksums) = template_checksums.get(server_checksums.tick) else {
+            // The tick is older than the template world's history, or from before a reset.
+            continue;
+        };
+
+        if local_checksums.checksums.len() != server_checksums.checksums.len() {
+            error!(
+                "Desync detected at {:?}: the server has {} types registered for checksums but the client has {}",
+                server_checksums.tick,
+                server_checksums.checksums.len(),
+                local_checksums.checksums.len(),
+            );
+
+            desyncs.write(SimulationDesync {
+                tick: server_checksums.tick,
+                type_name: None,
+            });
+
+            continue;
+        }
+
+        let mismatch = local_checksums
+            .checksums
+            .iter()
+            .zip(server_checksums.checksums.iter())
+            .position(|(local, server)| local != server);
+
+        if let Some(index) = mismatch {
+            let type_name = registry[index];
+
+            error!(
+                "Desync detected at {:?}: `{}` does not match the server",
+                server_checksums.tick, type_name,
+            );
+
+            desyncs.write(SimulationDesync {
+                tick: server_checksums.tick,
+                type_name: Some(type_name),
+            });
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Sends world updates created by the client to the server in [`SynchronizationMode::Lockstep`].
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    client::{
+        ClientPredictionSchedule, ClientPredictionStream, ClientSimulationSystems,
+        PredictionServerConnection,
+    },
+    common::{
+        ClientWorldUpdate,
+        loopback::PredictionSender,
+        scheme::SynchronizationMode,
+        simulation::{StepSimulationSystems, WorldUpdate},
+    },
+};
+
+pub(crate) fn build_update<T>(app: &mut App)
+where
+    T: Send + Sync + 'static + Serialize + Clone,
+{
+    let SynchronizationMode::Lockstep = app.world().resource::<SynchronizationMode>() else {
+        return;
+    };
+
+    let schedule = **app.world().resource::<ClientPredictionSchedule>();
+
+    app.init_resource::<OutgoingWorldUpdates<T>>();
+
+    app.add_systems(
+        schedule,
+        send_client_world_updates::<T>
+            .after(ClientSimulationSystems::QueueUpdates)
+            .before(StepSimulationSystems),
+    );
+}
+
+/// World updates created by the [`PredictionUpdateCreator`](crate::client::PredictionUpdateCreator)
+/// that haven't been sent to the server yet.
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct OutgoingWorldUpdates<T>(Vec<WorldUpdate<T>>);
+
+impl<T> Default for OutgoingWorldUpdates<T> {
+    fn default() -> Self {
+        OutgoingWorldUpdates(Vec::new())
+    }
+}
+
+fn send_client_world_updates<T>(
+    mut outgoing: ResMut<OutgoingWorldUpdates<T>>,
+    server_q: Query<Entity, With<PredictionServerConnection>>,
+    mut messages: PredictionSender<ClientPredictionStream>,
+) -> Result
+where
+    T: Send + Sync + 'static + Serialize + Clone,
+{
+    for update in outgoing.drain(..) {
+        for server_entity in &server_q {
+            messages.write(
+                server_entity,
+                true,
+                &ClientWorldUpdate {
+                    update: update.clone(),
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -5,26 +5,45 @@ use bevy::{
     prelude::*,
 };
 use nevy::prelude::*;
-use tracing::{debug, warn};
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::{debug, error, warn};
 
 use crate::{
     client::{
-        prediction::{PredictionUpdates, PredictionWorld},
+        desync::PendingServerChecksums,
+        lockstep::OutgoingWorldUpdates,
+        prediction::{PredictionExecution, PredictionUpdates, PredictionWorld},
+        recording::{RecordedMessage, SessionRecorder},
+        resync::LastAutoResync,
+        snapshot::SnapshotStatus,
+        spectator::Spectator,
         template_world::{ServerTickSamples, TemplateWorld},
     },
     common::{
-        ResetClientSimulation,
+        ClientProtocolFingerprint, ProtocolMismatch, ResetClientSimulation, ResumeSessionResult,
+        SessionToken,
+        clock::{PredictionClock, PredictionTimeSource, SimulationTimeScale},
+        loopback::{PredictionReceiver, PredictionSender},
         scheme::PredictionScheme,
         simulation::{
             PrivateSimulationTimeExt, SimulationInstance, SimulationPlugin, SimulationTick,
-            SimulationTime, SimulationTimeExt, StepSimulationSystems, UpdateExecutionQueue,
-            WorldUpdate, schedules::ResetSimulation,
+            SimulationTickRate, SimulationTime, SimulationTimeExt, StepSimulationSystems,
+            UpdateExecutionQueue, WorldUpdate, registry::SimulationRegistry,
+            schedules::ResetSimulation,
         },
     },
 };
 
+pub mod budget;
+pub mod desync;
+pub(crate) mod lockstep;
 pub mod prediction;
+pub mod recording;
+pub mod resync;
+pub mod session;
 pub(crate) mod simulation_world;
+pub mod snapshot;
+pub mod spectator;
 pub(crate) mod template_world;
 
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
@@ -48,6 +67,9 @@ pub(crate) struct ClientPredictionSchedule(pub Interned<dyn ScheduleLabel>);
 pub struct NevyPredictionClientPlugin<S> {
     pub(crate) _p: PhantomData<S>,
     pub(crate) schedule: Interned<dyn ScheduleLabel>,
+    pub(crate) time_source: PredictionTimeSource,
+    pub(crate) spectator: Option<Spectator>,
+    pub(crate) prediction_execution: PredictionExecution,
 }
 
 impl<S> Default for NevyPredictionClientPlugin<S> {
@@ -55,10 +77,39 @@ impl<S> Default for NevyPredictionClientPlugin<S> {
         NevyPredictionClientPlugin {
             _p: PhantomData,
             schedule: Update.intern(),
+            time_source: PredictionTimeSource::Real,
+            spectator: None,
+            prediction_execution: PredictionExecution::MainThread,
         }
     }
 }
 
+impl<S> NevyPredictionClientPlugin<S> {
+    /// Drives simulation time with a different clock.
+    ///
+    /// See the [`clock`](crate::common::clock) module.
+    pub fn with_time_source(mut self, time_source: PredictionTimeSource) -> Self {
+        self.time_source = time_source;
+        self
+    }
+
+    /// Makes the client a spectator that doesn't run client side prediction.
+    ///
+    /// See the [`spectator`] module.
+    pub fn with_spectator(mut self, spectator: Spectator) -> Self {
+        self.spectator = Some(spectator);
+        self
+    }
+
+    /// Controls where the prediction world is run.
+    ///
+    /// See [`PredictionExecution`].
+    pub fn with_prediction_execution(mut self, prediction_execution: PredictionExecution) -> Self {
+        self.prediction_execution = prediction_execution;
+        self
+    }
+}
+
 impl<S> Plugin for NevyPredictionClientPlugin<S>
 where
     S: PredictionScheme,
@@ -84,8 +135,26 @@ where
         );
 
         crate::common::build(app);
+        app.insert_resource(self.time_source);
+
+        if let Some(spectator) = self.spectator {
+            app.insert_resource(spectator);
+        }
+
+        app.add_shared_message_sender::<ClientPredictionStream>(
+            StreamRequirements::RELIABLE_ORDERED,
+        );
+
         template_world::build::<S>(app, self.schedule);
         prediction::build::<S>(app, self.schedule);
+        app.insert_resource(self.prediction_execution);
+        desync::build(app, self.schedule);
+        resync::build(app, self.schedule);
+        session::build(app, self.schedule);
+        snapshot::build(app, self.schedule);
+        recording::build::<S>(app, self.schedule);
+        spectator::build(app, self.schedule);
+        budget::build(app, self.schedule);
 
         app.add_plugins(SimulationPlugin::<S> {
             _p: PhantomData,
@@ -96,10 +165,10 @@ where
         app.add_systems(
             self.schedule,
             (
-                receive_reset_simulations
-                    .pipe(reset_simulations::<S>)
+                receive_reset_simulations::<S>
+                    .pipe::<_, _, Result, _>(reset_simulations::<S>)
                     .in_set(ClientSimulationSystems::ResetSimulation),
-                drive_simulation_time::<S>.in_set(ClientSimulationSystems::ReceiveUpdates),
+                drive_simulation_time.in_set(ClientSimulationSystems::ReceiveUpdates),
             ),
         );
 
@@ -112,18 +181,22 @@ where
 /// Is called on the client app for each world update message added by the prediction scheme
 pub(crate) fn build_update<T>(app: &mut App)
 where
-    T: Send + Sync + 'static + Clone,
+    T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone,
 {
     let schedule = **app.world().resource::<ClientPredictionSchedule>();
 
     template_world::build_update::<T>(app, schedule);
-    prediction::build_update::<T>(app);
+    prediction::build_update::<T>(app, schedule);
+    recording::build_update::<T>(app);
+    spectator::build_update::<T>(app, schedule);
 }
 
 /// Controls how many updates prediction logic is allowed to relative to the main app.
 ///
 /// These values should be greater than one to allow prediction logic to catch up,
 /// but if they are too high, too many updates may run in a single frame which cause hitching.
+///
+/// These rates aren't used while an [`AdaptivePredictionBudget`](budget::AdaptivePredictionBudget) exists.
 #[derive(Resource)]
 pub struct PredictionRates {
     pub template: f32,
@@ -153,25 +226,65 @@ struct PredictionBudget {
 #[derive(Resource, Default, Deref, DerefMut)]
 pub struct PredictionInterval(pub Duration);
 
+/// Marker type for the [SharedMessageSender] used to send prediction messages to the server.
+pub struct ClientPredictionStream;
+
 /// Marker component that must be inserted onto the server connection entity for prediction.
 #[derive(Component)]
 pub struct PredictionServerConnection;
 
-fn drive_simulation_time<S>(
-    server_time: Res<ServerTickSamples>,
+/// Estimates the server's simulation time from the [`ServerTickSamples`].
+#[derive(SystemParam)]
+struct ServerTimeEstimator<'w> {
+    samples: Res<'w, ServerTickSamples>,
+    template_world: Res<'w, TemplateWorld>,
+    clock: Res<'w, Time<PredictionClock>>,
+    time_scale: Res<'w, SimulationTimeScale>,
+}
+
+impl ServerTimeEstimator<'_> {
+    /// Server ticks are converted to time with the tick rate of the template world, which follows the server.
+    fn tick_rate(&self) -> SimulationTickRate {
+        self.template_world
+            .resource::<Time<SimulationTime>>()
+            .tick_rate()
+    }
+
+    fn time_scale(&self) -> f32 {
+        self.time_scale.effective()
+    }
+
+    fn estimated_time(&self) -> Duration {
+        self.samples
+            .estimated_time(self.tick_rate(), self.time_scale(), self.clock.elapsed())
+    }
+}
+
+fn drive_simulation_time(
+    server_time: ServerTimeEstimator,
     interval: Res<PredictionInterval>,
     mut time: ResMut<Time<SimulationTime>>,
-    real_time: Res<Time<Real>>,
     rates: Res<PredictionRates>,
     mut budget: ResMut<PredictionBudget>,
-) where
-    S: PredictionScheme,
-{
+    spectator: Option<Res<Spectator>>,
+) {
+    let server_tick_rate = server_time.tick_rate();
+
     loop {
-        let target_time = server_time.estimated_time::<S>(real_time.elapsed()) + **interval;
-        let current_time = time.target_tick().time::<S>();
+        let estimated_time = server_time.estimated_time();
+
+        let target_time = match &spectator {
+            // Spectators follow the server instead of predicting ahead of it.
+            Some(spectator) => {
+                estimated_time.saturating_sub(server_tick_rate.interval * spectator.delay)
+            }
+            // The prediction interval is real time, so it covers less simulation time when the simulation is slowed down.
+            // While paused this stops clients from predicting past the server.
+            None => estimated_time + interval.mul_f32(server_time.time_scale()),
+        };
+        let current_time = time.tick_time(time.target_tick());
 
-        if current_time + S::step_interval() > target_time {
+        if current_time + time.tick_rate().interval > target_time {
             break;
         }
 
@@ -195,61 +308,202 @@ fn drive_simulation_time<S>(
     }
 }
 
-fn receive_reset_simulations(
-    mut message_q: Query<(
-        Entity,
-        &mut ReceivedMessages<ResetClientSimulation>,
-        Has<PredictionServerConnection>,
-    )>,
-) -> Option<SimulationTick> {
+/// A message from the server that may reset the client's simulation.
+pub(crate) enum ReceivedReset {
+    Reset {
+        connection_entity: Entity,
+        simulation_tick: SimulationTick,
+        session: SessionToken,
+        snapshot: bool,
+        tick_rate: SimulationTickRate,
+    },
+    ResumeResult {
+        accepted: bool,
+        simulation_tick: SimulationTick,
+        snapshot: bool,
+        tick_rate: SimulationTickRate,
+    },
+}
+
+fn receive_reset_simulations<S>(
+    mut resets: PredictionReceiver<ResetClientSimulation, PredictionServerConnection>,
+    mut resume_results: PredictionReceiver<ResumeSessionResult, PredictionServerConnection>,
+    registry: Res<SimulationRegistry>,
+    mut mismatches: MessageWriter<ProtocolMismatch>,
+    mut messages: PredictionSender<ClientPredictionStream>,
+) -> Result<Option<ReceivedReset>>
+where
+    S: PredictionScheme,
+{
     let mut reset = None;
 
-    for (connection_entity, mut messages, is_server) in &mut message_q {
-        for ResetClientSimulation {
+    let local = registry.fingerprint::<S>();
+
+    for (
+        connection_entity,
+        is_server,
+        ResetClientSimulation {
             simulation_tick: simulation_time,
-        } in messages.drain()
-        {
-            if !is_server {
-                warn!(
-                    "Received a prediction message from a connection that isn't the server: {}",
-                    connection_entity
-                );
-
-                continue;
-            }
+            session,
+            snapshot,
+            fingerprint: remote,
+            tick_rate,
+        },
+    ) in resets.drain()
+    {
+        if !is_server {
+            warn!(
+                "Received a prediction message from a connection that isn't the server: {}",
+                connection_entity
+            );
+
+            continue;
+        }
 
-            reset = Some(simulation_time);
+        messages.write(
+            connection_entity,
+            true,
+            &ClientProtocolFingerprint { fingerprint: local },
+        )?;
+
+        if remote != local {
+            error!(
+                "The server has a different prediction protocol ({:?}) than the client ({:?}). \
+                World updates must be added in the same order with the same step interval on the client and server. \
+                The reset from the server was ignored.",
+                remote, local
+            );
+
+            mismatches.write(ProtocolMismatch {
+                connection_entity,
+                local,
+                remote,
+            });
+
+            continue;
         }
+
+        reset = Some(ReceivedReset::Reset {
+            connection_entity,
+            simulation_tick: simulation_time,
+            session,
+            snapshot,
+            tick_rate,
+        });
     }
 
-    reset
+    for (
+        connection_entity,
+        is_server,
+        ResumeSessionResult {
+            accepted,
+            simulation_tick,
+            snapshot,
+            tick_rate,
+        },
+    ) in resume_results.drain()
+    {
+        if !is_server {
+            warn!(
+                "Received a prediction message from a connection that isn't the server: {}",
+                connection_entity
+            );
+
+            continue;
+        }
+
+        reset = Some(ReceivedReset::ResumeResult {
+            accepted,
+            simulation_tick,
+            snapshot,
+            tick_rate,
+        });
+    }
+
+    Ok(reset)
 }
 
-fn reset_simulations<S>(In(reset_tick): In<Option<SimulationTick>>, world: &mut World)
+fn reset_simulations<S>(In(reset): In<Result<Option<ReceivedReset>>>, world: &mut World) -> Result
 where
     S: PredictionScheme,
 {
-    let Some(reset_tick) = reset_tick else {
-        return;
-    };
+    match reset? {
+        None => (),
+        Some(ReceivedReset::Reset {
+            connection_entity,
+            simulation_tick,
+            session,
+            snapshot,
+            tick_rate,
+        }) => {
+            if session::try_resume::<S>(world, connection_entity, simulation_tick, session) {
+                return Ok(());
+            }
+
+            reset_client_simulation::<S>(world, simulation_tick, tick_rate, snapshot);
+        }
+        Some(ReceivedReset::ResumeResult {
+            accepted,
+            simulation_tick,
+            snapshot,
+            tick_rate,
+        }) => {
+            if accepted {
+                debug!("resumed previous prediction session");
+                return Ok(());
+            }
+
+            debug!("server refused to resume the previous prediction session");
+
+            reset_client_simulation::<S>(world, simulation_tick, tick_rate, snapshot);
+        }
+    }
 
+    Ok(())
+}
+
+pub(crate) fn reset_client_simulation<S>(
+    world: &mut World,
+    reset_tick: SimulationTick,
+    tick_rate: SimulationTickRate,
+    snapshot: bool,
+) where
+    S: PredictionScheme,
+{
     debug!("resetting simulation to {:?}", reset_tick);
 
-    world.resource_mut::<TemplateWorld>().reset::<S>(reset_tick);
+    let current_time = world.resource::<Time<PredictionClock>>().elapsed();
+    world.resource_mut::<SessionRecorder>().record(
+        current_time,
+        RecordedMessage::Reset {
+            simulation_tick: reset_tick,
+            tick_rate,
+            snapshot,
+        },
+    );
+
+    world
+        .resource_mut::<TemplateWorld>()
+        .reset(reset_tick, tick_rate);
 
     world
         .resource_mut::<PredictionWorld>()
-        .reset::<S>(reset_tick);
+        .reset(reset_tick, tick_rate);
 
-    world.insert_resource(Time::<SimulationTime>::from_tick::<S>(reset_tick));
+    world.insert_resource(Time::<SimulationTime>::from_tick(reset_tick, tick_rate));
     world.run_schedule(ResetSimulation);
 
     world.init_resource::<PredictionBudget>();
+    world.resource_mut::<PendingServerChecksums>().clear();
+    world.insert_resource(LastAutoResync::default());
+    world.insert_resource(match snapshot {
+        true => SnapshotStatus::Awaiting,
+        false => SnapshotStatus::Complete,
+    });
 
-    let real_time = world.resource::<Time<Real>>().elapsed();
     world
         .resource_mut::<ServerTickSamples>()
-        .reset::<S>(real_time, reset_tick);
+        .reset::<S>(current_time, reset_tick);
 }
 
 #[derive(SystemParam)]
@@ -259,7 +513,9 @@ where
 {
     time: Res<'w, Time<SimulationTime>>,
     simulation_queue: ResMut<'w, UpdateExecutionQueue<T>>,
-    prediction_world: ResMut<'w, PredictionWorld>,
+    /// Doesn't exist with the [`NevyPredictionOfflinePlugin`](crate::offline::NevyPredictionOfflinePlugin).
+    prediction_updates: Option<ResMut<'w, PredictionUpdates<T>>>,
+    outgoing: Option<ResMut<'w, OutgoingWorldUpdates<T>>>,
 }
 
 impl<'w, T> PredictionUpdateCreator<'w, T>
@@ -275,6 +531,12 @@ where
     /// It should be noted that you do not need to use the world update returned by this function in your network message.
     /// If the server can infer what the update is based on which client sent it,
     /// then all that needs to be communicated is the timestamp of the returned update.
+    ///
+    /// In [`SynchronizationMode::Lockstep`](crate::common::scheme::SynchronizationMode::Lockstep)
+    /// the update is sent to the server automatically.
+    ///
+    /// With the [`NevyPredictionOfflinePlugin`](crate::offline::NevyPredictionOfflinePlugin)
+    /// the update is only applied to the local simulation, which is authoritative.
     pub fn create(&mut self, update: T) -> WorldUpdate<T> {
         let update = WorldUpdate {
             tick: self.time.current_tick(),
@@ -282,9 +544,14 @@ where
         };
 
         self.simulation_queue.insert(update.clone());
-        self.prediction_world
-            .resource_mut::<PredictionUpdates<T>>()
-            .push_back(update.clone());
+
+        if let Some(prediction_updates) = &mut self.prediction_updates {
+            prediction_updates.push_back(update.clone());
+        }
+
+        if let Some(outgoing) = &mut self.outgoing {
+            outgoing.push(update.clone());
+        }
 
         update
     }
@@ -1,24 +1,37 @@
+//! The prediction world predicts the main world's ticks, starting from the template world.
+//!
+//! Every time the template world advances, the prediction world is reset to it
+//! and re-simulates up to the main world's tick with the updates the client has created,
+//! then the result is extracted into the main world.
+//!
+//! The prediction world can run on the main thread or as a background task, see [`PredictionExecution`].
+//! Only the prediction world can run in the background, the template world always runs on the main thread.
+
 use std::{marker::PhantomData, ops::DerefMut};
 
 use bevy::{
-    ecs::{intern::Interned, schedule::ScheduleLabel},
+    ecs::{entity_disabling::Disabled, intern::Interned, schedule::ScheduleLabel},
+    platform::{collections::HashSet, time::Instant},
     prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
 };
 use tracing::error;
 
 use crate::{
     client::{
-        ClientSimulationSystems, PredictionBudget, simulation_world::SimulationWorld,
+        ClientSimulationSystems, PredictionBudget, budget::SimulationTickCosts,
+        simulation_world::SimulationWorld, snapshot::SnapshotStatus, spectator::Spectator,
         template_world::TemplateWorld,
     },
     common::{
         scheme::PredictionScheme,
         simulation::{
             PrivateSimulationTimeExt, SimulationInstance, SimulationPlugin, SimulationTick,
-            SimulationTime, SimulationTimeExt, UpdateExecutionQueue, WorldUpdateQueue,
-            schedules::SimulationPreUpdate,
+            SimulationTickRate, SimulationTime, SimulationTimeExt, UpdateExecutionQueue,
+            WorldUpdateQueue, schedules::SimulationPreUpdate, simulation_entity::SimulationEntity,
         },
     },
+    diagnostics::PredictionFrameStats,
 };
 
 pub(crate) fn build<S>(app: &mut App, schedule: Interned<dyn ScheduleLabel>)
@@ -27,32 +40,124 @@ where
 {
     app.insert_resource(PredictionWorld::new::<S>());
     app.init_resource::<LastPredictedTick>();
+    app.init_resource::<PredictionSubset>();
+    app.init_resource::<PredictionUpdateTransfers>();
 
     app.add_systems(
         schedule,
-        run_prediction_world.in_set(ClientSimulationSystems::RunPredictionWorld),
+        run_prediction_world
+            .in_set(ClientSimulationSystems::RunPredictionWorld)
+            .run_if(not(resource_exists::<Spectator>)),
     );
 }
 
-pub(crate) fn build_update<T>(app: &mut App)
+pub(crate) fn build_update<T>(app: &mut App, schedule: Interned<dyn ScheduleLabel>)
 where
     T: Send + Sync + 'static + Clone,
 {
+    app.init_resource::<PredictionUpdates<T>>();
+    let mut transfers = app.world_mut().resource_mut::<PredictionUpdateTransfers>();
+    transfers.copies.push(copy_prediction_updates::<T>);
+    transfers.swaps.push(swap_prediction_updates::<T>);
+
+    app.add_systems(
+        schedule,
+        drain_prediction_updates::<T>.in_set(ClientSimulationSystems::QueuePredictionUpdates),
+    );
+
     let mut prediction_world = app.world_mut().resource_mut::<PredictionWorld>();
 
     prediction_world.init_resource::<PredictionUpdates<T>>();
-    prediction_world.resource_mut::<Schedules>().add_systems(
-        SimulationPreUpdate,
-        (drain_prediction_updates::<T>, queue_prediction_updates::<T>).chain(),
-    );
+    prediction_world
+        .resource_mut::<Schedules>()
+        .add_systems(SimulationPreUpdate, queue_prediction_updates::<T>);
 }
 
 /// The last tick that the prediction world predicted from.
 ///
-/// When prediction starts this resource is copied into the prediction world and holds which tick prediction started from.
+/// Prediction updates before this tick have been applied to the template world and are removed.
 #[derive(Resource, Default, Clone, Deref, DerefMut)]
 struct LastPredictedTick(SimulationTick);
 
+/// Controls where the prediction world is run.
+///
+/// Changes take effect when the next prediction sequence starts.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PredictionExecution {
+    /// Runs the prediction world on the main thread, limited by the [`PredictionRates`](crate::client::PredictionRates).
+    #[default]
+    MainThread,
+    /// Runs each prediction sequence as a task on the [`AsyncComputeTaskPool`],
+    /// and extracts the result into the main world in the first frame after it finishes.
+    ///
+    /// Heavy re-simulation no longer causes frame hitches, but the predicted state arrives a frame or more later,
+    /// and world updates created while a sequence is running are only predicted from the next sequence.
+    /// The [`PredictionRates`](crate::client::PredictionRates) don't limit background prediction.
+    ///
+    /// Only the prediction world is run in the background.
+    /// The template world always runs on the main thread,
+    /// because world updates from the server are queued into it and it is read by other client logic every frame.
+    ///
+    /// A reset while a sequence is running doesn't wait for it,
+    /// the prediction world is reset when the task finishes and its result is discarded.
+    Background,
+}
+
+/// Controls which [`SimulationEntity`]s are simulated by the prediction world.
+///
+/// By default every entity is predicted.
+/// In a large world most entities can't be affected by the local player,
+/// so predicting only the local player's entities and the entities near them can save a lot of work.
+///
+/// Entities outside of the subset are [`Disabled`] in the prediction world, so the simulation's queries don't see them,
+/// and they are extracted into the main world from the [`TemplateWorld`] unchanged.
+/// Changes to the subset take effect when the next prediction sequence starts.
+#[derive(Resource, Default)]
+pub struct PredictionSubset {
+    entities: Option<HashSet<SimulationEntity>>,
+}
+
+impl PredictionSubset {
+    /// Predicts every entity.
+    pub fn all(&mut self) {
+        self.entities = None;
+    }
+
+    /// Predicts only the given entities.
+    pub fn only(&mut self, entities: impl IntoIterator<Item = SimulationEntity>) {
+        self.entities = Some(entities.into_iter().collect());
+    }
+
+    /// Adds an entity to the subset.
+    ///
+    /// Does nothing if every entity is predicted.
+    pub fn insert(&mut self, entity: SimulationEntity) {
+        if let Some(entities) = &mut self.entities {
+            entities.insert(entity);
+        }
+    }
+
+    /// Removes an entity from the subset.
+    ///
+    /// Does nothing if every entity is predicted.
+    pub fn remove(&mut self, entity: SimulationEntity) {
+        if let Some(entities) = &mut self.entities {
+            entities.remove(&entity);
+        }
+    }
+
+    pub fn contains(&self, entity: SimulationEntity) -> bool {
+        self.entities
+            .as_ref()
+            .is_none_or(|entities| entities.contains(&entity))
+    }
+
+    /// Returns `true` if only some entities are predicted.
+    pub fn is_partial(&self) -> bool {
+        self.entities.is_some()
+    }
+}
+
 /// Contains the [`ParallelWorld`] used for prediction.
 #[derive(Resource, Deref, DerefMut)]
 pub(crate) struct PredictionWorld {
@@ -61,10 +166,18 @@ pub(crate) struct PredictionWorld {
     state: PredictionWorldState,
 }
 
-#[derive(Clone, Copy)]
 enum PredictionWorldState {
     Idle,
     Running,
+    /// The world is running in a task, and [`PredictionWorld::world`] is empty.
+    Background(Task<SimulationWorld>),
+    /// The simulation was reset while the world was running in a task.
+    /// The world is reset once the task finishes.
+    ResetPending {
+        task: Task<SimulationWorld>,
+        tick: SimulationTick,
+        tick_rate: SimulationTickRate,
+    },
 }
 
 impl PredictionWorld {
@@ -89,11 +202,27 @@ impl PredictionWorld {
         }
     }
 
-    pub fn reset<S>(&mut self, tick: SimulationTick)
-    where
-        S: PredictionScheme,
-    {
-        self.world.reset::<S>(tick);
+    /// Resets the prediction world, or defers the reset until a running background task finishes.
+    pub fn reset(&mut self, tick: SimulationTick, tick_rate: SimulationTickRate) {
+        match std::mem::replace(&mut self.state, PredictionWorldState::Idle) {
+            PredictionWorldState::Background(task)
+            | PredictionWorldState::ResetPending { task, .. } => {
+                self.state = PredictionWorldState::ResetPending {
+                    task,
+                    tick,
+                    tick_rate,
+                };
+            }
+            PredictionWorldState::Idle | PredictionWorldState::Running => {
+                self.reset_world(tick, tick_rate);
+            }
+        }
+    }
+
+    fn reset_world(&mut self, tick: SimulationTick, tick_rate: SimulationTickRate) {
+        // Disabled entities wouldn't be despawned by the reset.
+        enable_simulation_entities(&mut self.world);
+        self.world.reset(tick, tick_rate);
         self.state = PredictionWorldState::Idle;
     }
 }
@@ -102,8 +231,13 @@ fn run_prediction_world(world: &mut World) {
     let mut prediction_world = world.remove_resource::<PredictionWorld>().unwrap();
 
     loop {
-        match prediction_world.state {
+        match &mut prediction_world.state {
             PredictionWorldState::Idle => {
+                // Prediction from a partial snapshot would be thrown away.
+                if !world.resource::<SnapshotStatus>().is_complete() {
+                    break;
+                }
+
                 let current_template_tick = world
                     .resource::<TemplateWorld>()
                     .resource::<Time<SimulationTime>>()
@@ -118,12 +252,19 @@ fn run_prediction_world(world: &mut World) {
                 // Start a prediction sequence.
 
                 **last_predicted_tick = current_template_tick;
-                prediction_world.insert_resource(last_predicted_tick.clone());
+
+                // Entities disabled by the last sequence need to be visible to the extract schedule.
+                enable_simulation_entities(&mut prediction_world);
 
                 world
                     .resource_mut::<TemplateWorld>()
                     .extract(prediction_world.deref_mut());
 
+                disable_unpredicted_entities(
+                    &mut prediction_world,
+                    world.resource::<PredictionSubset>(),
+                );
+
                 prediction_world
                     .resource_mut::<Time<SimulationTime>>()
                     .clear_target();
@@ -134,7 +275,27 @@ fn run_prediction_world(world: &mut World) {
                 let current_tick = prediction_world
                     .resource::<Time<SimulationTime>>()
                     .current_tick();
-                let desired_tick = world.resource::<Time<SimulationTime>>().current_tick();
+                // The main world's current tick is reset by extracting a finished background sequence,
+                // so its target tick is predicted to instead.
+                let desired_tick = world.resource::<Time<SimulationTime>>().target_tick();
+
+                if let PredictionExecution::Background = *world.resource::<PredictionExecution>() {
+                    // The task needs its own copy of the updates, because the main world keeps changing them.
+                    copy_all_prediction_updates(world, &mut prediction_world);
+
+                    let execute_ticks = desired_tick.saturating_sub(*current_tick);
+                    let mut simulation_world = std::mem::take(&mut prediction_world.world);
+
+                    record_resimulated_ticks(world, execute_ticks);
+
+                    let task = AsyncComputeTaskPool::get().spawn(async move {
+                        simulation_world.run(execute_ticks);
+                        simulation_world
+                    });
+
+                    prediction_world.state = PredictionWorldState::Background(task);
+                    break;
+                }
 
                 let mut budget = world.resource_mut::<PredictionBudget>();
 
@@ -147,7 +308,16 @@ fn run_prediction_world(world: &mut World) {
                 let execute_ticks = desired_ticks.min(budget.prediction);
 
                 budget.prediction -= execute_ticks;
+
+                let start = Instant::now();
+                swap_all_prediction_updates(world, &mut prediction_world);
                 prediction_world.run(execute_ticks);
+                swap_all_prediction_updates(world, &mut prediction_world);
+                world
+                    .resource_mut::<SimulationTickCosts>()
+                    .record_prediction(start.elapsed(), execute_ticks);
+                record_resimulated_ticks(world, execute_ticks);
+                forward_late_updates(world, &mut prediction_world);
 
                 if prediction_world
                     .resource::<Time<SimulationTime>>()
@@ -161,19 +331,95 @@ fn run_prediction_world(world: &mut World) {
                         );
                     }
 
-                    prediction_world.extract(world);
-                    prediction_world.state = PredictionWorldState::Idle;
+                    finish_prediction_sequence(world, &mut prediction_world);
                 }
             }
+            PredictionWorldState::Background(task) => {
+                let Some(simulation_world) = block_on(future::poll_once(task)) else {
+                    break;
+                };
+
+                prediction_world.world = simulation_world;
+                forward_late_updates(world, &mut prediction_world);
+                finish_prediction_sequence(world, &mut prediction_world);
+            }
+            PredictionWorldState::ResetPending {
+                task,
+                tick,
+                tick_rate,
+            } => {
+                let (tick, tick_rate) = (*tick, *tick_rate);
+
+                let Some(simulation_world) = block_on(future::poll_once(task)) else {
+                    break;
+                };
+
+                // The result was predicted from before the reset, so it is discarded.
+                prediction_world.world = simulation_world;
+                prediction_world.reset_world(tick, tick_rate);
+            }
         }
     }
 
     world.insert_resource(prediction_world);
 }
 
+fn record_resimulated_ticks(world: &mut World, ticks: u32) {
+    if let Some(mut stats) = world.get_resource_mut::<PredictionFrameStats>() {
+        stats.resimulated_ticks += ticks;
+    }
+}
+
+fn forward_late_updates(world: &mut World, prediction_world: &mut PredictionWorld) {
+    world.write_message_batch(prediction_world.world.drain_late_updates());
+}
+
+/// Extracts the prediction world into the main world.
+fn finish_prediction_sequence(world: &mut World, prediction_world: &mut PredictionWorld) {
+    // Entities that weren't predicted are taken from the template world,
+    // and then the predicted entities are extracted over them.
+    if world.resource::<PredictionSubset>().is_partial() {
+        world.resource_scope(|world, mut template_world: Mut<TemplateWorld>| {
+            template_world.extract(world);
+        });
+    }
+
+    prediction_world.extract(world);
+    prediction_world.state = PredictionWorldState::Idle;
+}
+
+fn enable_simulation_entities(world: &mut World) {
+    let disabled_entities: Vec<_> = world
+        .query_filtered::<Entity, (With<SimulationEntity>, With<Disabled>)>()
+        .iter(world)
+        .collect();
+
+    for entity in disabled_entities {
+        world.entity_mut(entity).remove::<Disabled>();
+    }
+}
+
+fn disable_unpredicted_entities(world: &mut World, subset: &PredictionSubset) {
+    if !subset.is_partial() {
+        return;
+    }
+
+    let unpredicted_entities: Vec<_> = world
+        .query::<(Entity, &SimulationEntity)>()
+        .iter(world)
+        .filter(|&(_, &simulation_entity)| !subset.contains(simulation_entity))
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in unpredicted_entities {
+        world.entity_mut(entity).insert(Disabled);
+    }
+}
+
 /// Contains a sorted list of world updates that haven't been reconciled with the server.
 ///
-/// This resource is inserted into the [`PredictionWorld`] and updates of the matching frame add added to the [`UpdateExecutionQueue`] every update.
+/// This resource is in the main world, and is copied into the [`PredictionWorld`] before it runs,
+/// where updates of the matching tick are added to the [`UpdateExecutionQueue`] every tick.
 #[derive(Resource, Deref, DerefMut)]
 pub(crate) struct PredictionUpdates<T>(WorldUpdateQueue<T>);
 
@@ -199,6 +445,55 @@ fn drain_prediction_updates<T>(
     }
 }
 
+/// Moves the [`PredictionUpdates`] of each world update type from the main world into the prediction world.
+///
+/// On the main thread the updates are swapped into the prediction world while it runs,
+/// and a background task is given a copy.
+#[derive(Resource, Default)]
+struct PredictionUpdateTransfers {
+    copies: Vec<fn(&World, &mut World)>,
+    swaps: Vec<fn(&mut World, &mut World)>,
+}
+
+fn copy_all_prediction_updates(world: &World, prediction_world: &mut World) {
+    for copy in world.resource::<PredictionUpdateTransfers>().copies.iter() {
+        copy(world, prediction_world);
+    }
+}
+
+fn swap_all_prediction_updates(world: &mut World, prediction_world: &mut World) {
+    world.resource_scope(|world, transfers: Mut<PredictionUpdateTransfers>| {
+        for swap in transfers.swaps.iter() {
+            swap(world, prediction_world);
+        }
+    });
+}
+
+fn copy_prediction_updates<T>(world: &World, prediction_world: &mut World)
+where
+    T: Send + Sync + 'static + Clone,
+{
+    let mut copied = PredictionUpdates::<T>::default();
+
+    for update in world.resource::<PredictionUpdates<T>>().iter() {
+        copied.push_back(update.clone());
+    }
+
+    prediction_world.insert_resource(copied);
+}
+
+fn swap_prediction_updates<T>(world: &mut World, prediction_world: &mut World)
+where
+    T: Send + Sync + 'static,
+{
+    std::mem::swap(
+        world.resource_mut::<PredictionUpdates<T>>().as_mut(),
+        prediction_world
+            .resource_mut::<PredictionUpdates<T>>()
+            .as_mut(),
+    );
+}
+
 /// Runs in [`SimulationPreUpdate`] on the prediction app.
 ///
 /// Queues any updates that should happen this tick on the prediction app.
@@ -0,0 +1,342 @@
+//! Records the prediction messages a client receives so that a session can be replayed offline.
+//!
+//! While the [`SessionRecorder`] is recording, every world update, server tick update and reset
+//! the client receives is stored with the time it was received.
+//! A recording always starts with a reset, because the state of a simulation that is already running can't be recorded.
+//! The resulting [`SessionRecording`] can be saved to a file and attached to a bug report.
+//!
+//! To replay a recording, build an app with the [`NevyPredictionClientPlugin`](crate::client::NevyPredictionClientPlugin)
+//! and no server connection, and insert a [`SessionReplay`].
+//! The recorded messages are then fed into the [`TemplateWorld`] and [`PredictionWorld`](crate::client::prediction::PredictionWorld)
+//! at the same times relative to the start of the replay as they were received.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    time::Duration,
+};
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::debug;
+
+use crate::{
+    client::{
+        ClientSimulationSystems,
+        prediction::PredictionUpdates,
+        reset_client_simulation,
+        snapshot::SnapshotStatus,
+        template_world::{ServerTickSamples, TemplateWorld, apply_time_scale, queue_server_update},
+    },
+    common::{
+        clock::{PredictionClock, SimulationTimeScale},
+        scheme::PredictionScheme,
+        simulation::{SimulationTick, SimulationTickRate, WorldUpdate},
+    },
+};
+
+pub(crate) fn build<S>(app: &mut App, schedule: Interned<dyn ScheduleLabel>)
+where
+    S: PredictionScheme,
+{
+    app.init_resource::<SessionRecorder>();
+    app.init_resource::<ReplayUpdateRegistry>();
+
+    app.add_systems(
+        schedule,
+        replay_session::<S>.in_set(ClientSimulationSystems::ReceiveUpdates),
+    );
+}
+
+pub(crate) fn build_update<T>(app: &mut App)
+where
+    T: Send + Sync + 'static + DeserializeOwned + Clone,
+{
+    app.world_mut()
+        .resource_mut::<ReplayUpdateRegistry>()
+        .insert(std::any::type_name::<T>(), replay_world_update::<T>);
+}
+
+/// A message received by the client, as stored in a [`SessionRecording`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum RecordedMessage {
+    /// The client reset its simulation.
+    Reset {
+        simulation_tick: SimulationTick,
+        tick_rate: SimulationTickRate,
+        snapshot: bool,
+    },
+    /// The server's simulation reached a tick.
+    ServerTick { simulation_tick: SimulationTick },
+    /// The server sent a world update.
+    WorldUpdate {
+        /// The type name of the world update.
+        update_type: String,
+        include_in_prediction: bool,
+        /// The serialized [`WorldUpdate`].
+        data: Vec<u8>,
+    },
+    /// The server finished sending an initial snapshot.
+    SnapshotComplete { simulation_tick: SimulationTick },
+    /// The server changed its time scale.
+    TimeScale { time_scale: SimulationTimeScale },
+}
+
+/// A [`RecordedMessage`] with the time it was received, relative to the first message of the recording.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedEntry {
+    pub time: Duration,
+    pub message: RecordedMessage,
+}
+
+/// The messages received by a client during a session.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SessionRecording {
+    pub entries: Vec<RecordedEntry>,
+}
+
+impl SessionRecording {
+    /// Writes the recording to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result {
+        let file = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(file, self)?;
+
+        Ok(())
+    }
+
+    /// Reads a recording from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+
+        Ok(bincode::deserialize_from(file)?)
+    }
+}
+
+/// Records the prediction messages received by the client.
+///
+/// Nothing is recorded until [`SessionRecorder::start`] is called.
+/// If it's started during a session, messages are ignored until the server next resets the client's simulation,
+/// e.g. when it reconnects.
+#[derive(Resource, Default)]
+pub struct SessionRecorder {
+    recording: Option<SessionRecording>,
+    start_time: Option<Duration>,
+}
+
+impl SessionRecorder {
+    /// Starts a new recording, discarding any recording in progress.
+    pub fn start(&mut self) {
+        self.recording = Some(SessionRecording::default());
+        self.start_time = None;
+    }
+
+    /// Stops recording, returning the recording if one was in progress.
+    pub fn stop(&mut self) -> Option<SessionRecording> {
+        self.start_time = None;
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Returns `true` if the recorder was started but hasn't received a reset yet.
+    pub fn is_awaiting_reset(&self) -> bool {
+        self.recording
+            .as_ref()
+            .is_some_and(|recording| recording.entries.is_empty())
+    }
+
+    /// Records a message that was received at `received_time`.
+    pub(crate) fn record(&mut self, received_time: Duration, message: RecordedMessage) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        if recording.entries.is_empty() && !matches!(message, RecordedMessage::Reset { .. }) {
+            return;
+        }
+
+        let start_time = *self.start_time.get_or_insert(received_time);
+
+        recording.entries.push(RecordedEntry {
+            time: received_time.saturating_sub(start_time),
+            message,
+        });
+    }
+
+    /// Records a world update that was received at `received_time`.
+    pub(crate) fn record_update<T>(
+        &mut self,
+        received_time: Duration,
+        include_in_prediction: bool,
+        update: &WorldUpdate<T>,
+    ) -> Result
+    where
+        T: Serialize,
+    {
+        if !self.is_recording() || self.is_awaiting_reset() {
+            return Ok(());
+        }
+
+        let data = bincode::serialize(update)?;
+
+        self.record(
+            received_time,
+            RecordedMessage::WorldUpdate {
+                update_type: std::any::type_name::<T>().into(),
+                include_in_prediction,
+                data,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+/// Insert this resource to replay a [`SessionRecording`].
+///
+/// The replay starts on the first frame after it's inserted.
+#[derive(Resource)]
+pub struct SessionReplay {
+    recording: SessionRecording,
+    cursor: usize,
+    start_time: Option<Duration>,
+}
+
+impl SessionReplay {
+    pub fn new(recording: SessionRecording) -> Self {
+        SessionReplay {
+            recording,
+            cursor: 0,
+            start_time: None,
+        }
+    }
+
+    /// Returns `true` once every recorded message has been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.recording.entries.len()
+    }
+}
+
+/// Deserializes a recorded world update and queues it, see [`replay_world_update`].
+type ReplayWorldUpdate = fn(&mut World, bool, &[u8]) -> Result;
+
+/// Deserializes and queues recorded world updates by their type name.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct ReplayUpdateRegistry(HashMap<&'static str, ReplayWorldUpdate>);
+
+fn replay_world_update<T>(world: &mut World, include_in_prediction: bool, data: &[u8]) -> Result
+where
+    T: Send + Sync + 'static + DeserializeOwned + Clone,
+{
+    let update: WorldUpdate<T> = bincode::deserialize(data)?;
+
+    world.resource_scope(|world, mut template_world: Mut<TemplateWorld>| {
+        let mut prediction_updates = world.resource_mut::<PredictionUpdates<T>>();
+
+        queue_server_update(
+            &mut template_world,
+            &mut prediction_updates,
+            include_in_prediction,
+            update,
+        );
+    });
+
+    Ok(())
+}
+
+fn replay_session<S>(world: &mut World) -> Result
+where
+    S: PredictionScheme,
+{
+    let Some(mut replay) = world.remove_resource::<SessionReplay>() else {
+        return Ok(());
+    };
+
+    let current_time = world.resource::<Time<PredictionClock>>().elapsed();
+    let elapsed = current_time.saturating_sub(*replay.start_time.get_or_insert(current_time));
+
+    let result = replay_due_entries::<S>(world, &mut replay, current_time, elapsed);
+
+    if replay.is_finished() {
+        debug!("finished replaying session");
+    }
+
+    world.insert_resource(replay);
+
+    result
+}
+
+fn replay_due_entries<S>(
+    world: &mut World,
+    replay: &mut SessionReplay,
+    current_time: Duration,
+    elapsed: Duration,
+) -> Result
+where
+    S: PredictionScheme,
+{
+    while let Some(entry) = replay.recording.entries.get(replay.cursor) {
+        if entry.time > elapsed {
+            break;
+        }
+
+        replay.cursor += 1;
+
+        match &entry.message {
+            &RecordedMessage::Reset {
+                simulation_tick,
+                tick_rate,
+                snapshot,
+            } => {
+                reset_client_simulation::<S>(world, simulation_tick, tick_rate, snapshot);
+            }
+            &RecordedMessage::ServerTick { simulation_tick } => {
+                world
+                    .resource_mut::<ServerTickSamples>()
+                    .push::<S>(current_time, simulation_tick);
+            }
+            RecordedMessage::WorldUpdate {
+                update_type,
+                include_in_prediction,
+                data,
+            } => {
+                let replay_update = world
+                    .resource::<ReplayUpdateRegistry>()
+                    .get(update_type.as_str())
+                    .copied()
+                    .ok_or(format!(
+                        "The recording contains a world update `{}` that hasn't been added",
+                        update_type
+                    ))?;
+
+                replay_update(world, *include_in_prediction, data)?;
+            }
+            &RecordedMessage::TimeScale { time_scale: update } => {
+                world.resource_scope(|world, mut time_scale: Mut<SimulationTimeScale>| {
+                    apply_time_scale::<S>(
+                        &mut time_scale,
+                        &mut world.resource_mut::<ServerTickSamples>(),
+                        current_time,
+                        update,
+                    );
+                });
+            }
+            &RecordedMessage::SnapshotComplete { simulation_tick } => {
+                let mut status = world.resource_mut::<SnapshotStatus>();
+
+                if let SnapshotStatus::Awaiting = *status {
+                    *status = SnapshotStatus::Received(simulation_tick);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Requests resyncs of the [`TemplateWorld`](crate::client::template_world::TemplateWorld) from the server.
+//!
+//! See the [`resync`](crate::common::simulation::resync) module.
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    prelude::*,
+};
+
+use crate::{
+    client::{
+        ClientPredictionStream, PredictionServerConnection,
+        desync::{SimulationDesync, compare_checksums},
+        template_world::ServerTickSamples,
+    },
+    common::{
+        ResyncRequest,
+        loopback::PredictionSender,
+        simulation::{SimulationTick, resync::ResyncTarget},
+    },
+};
+
+pub(crate) fn build(app: &mut App, schedule: Interned<dyn ScheduleLabel>) {
+    app.add_message::<RequestResync>();
+    app.init_resource::<AutoResync>();
+    app.init_resource::<LastAutoResync>();
+
+    app.add_systems(
+        schedule,
+        (resync_on_desync, send_resync_requests)
+            .chain()
+            .after(compare_checksums),
+    );
+}
+
+/// Write this message to request that the server resends part of the simulation.
+///
+/// The server responds with world updates at its current tick,
+/// so the resync is applied to the template world without resetting the simulation.
+/// Only components registered with a [`ResyncComponentPlugin`](crate::common::simulation::resync::ResyncComponentPlugin) are resent.
+/// The server drops requests made within its [`ResyncRequestCooldown`](crate::server::resync::ResyncRequestCooldown).
+#[derive(Message, Clone, Debug)]
+pub struct RequestResync {
+    pub target: ResyncTarget,
+}
+
+/// Controls whether a [`SimulationDesync`] automatically requests a resync of the whole world.
+///
+/// Enabled by default.
+#[derive(Resource, Deref, DerefMut)]
+pub struct AutoResync(pub bool);
+
+impl Default for AutoResync {
+    fn default() -> Self {
+        AutoResync(true)
+    }
+}
+
+/// The server tick at the time of the last automatic resync.
+///
+/// Desyncs detected before this tick are ignored, because the resync hasn't been applied yet.
+#[derive(Resource, Default)]
+pub(crate) struct LastAutoResync(Option<SimulationTick>);
+
+fn resync_on_desync(
+    auto_resync: Res<AutoResync>,
+    mut last_resync: ResMut<LastAutoResync>,
+    server_ticks: Res<ServerTickSamples>,
+    mut desyncs: MessageReader<SimulationDesync>,
+    mut requests: MessageWriter<RequestResync>,
+) {
+    let mut desynced = false;
+
+    for desync in desyncs.read() {
+        if last_resync.0.is_some_and(|tick| desync.tick <= tick) {
+            continue;
+        }
+
+        desynced = true;
+    }
+
+    if !**auto_resync || !desynced {
+        return;
+    }
+
+    last_resync.0 = Some(server_ticks.latest());
+
+    requests.write(RequestResync {
+        target: ResyncTarget::World,
+    });
+}
+
+fn send_resync_requests(
+    mut requests: MessageReader<RequestResync>,
+    server_q: Query<Entity, With<PredictionServerConnection>>,
+    mut messages: PredictionSender<ClientPredictionStream>,
+) -> Result {
+    for RequestResync { target } in requests.read() {
+        for server_entity in &server_q {
+            messages.write(
+                server_entity,
+                true,
+                &ResyncRequest {
+                    target: target.clone(),
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! Resumes the previous prediction session when the client reconnects to the server.
+//!
+//! See the [`session`](crate::server::session) module of the server.
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    prelude::*,
+};
+use tracing::debug;
+
+use crate::{
+    client::{
+        ClientPredictionStream, ClientSimulationSystems,
+        template_world::{ServerTickSamples, TemplateWorld},
+    },
+    common::{
+        ResumeSession, SessionToken,
+        clock::PredictionClock,
+        loopback::PredictionSender,
+        scheme::PredictionScheme,
+        simulation::{SimulationTick, SimulationTime, SimulationTimeExt},
+    },
+};
+
+pub(crate) fn build(app: &mut App, schedule: Interned<dyn ScheduleLabel>) {
+    app.init_resource::<ResumeSessions>();
+    app.init_resource::<ClientSession>();
+
+    app.add_systems(
+        schedule,
+        send_resume_requests.in_set(ClientSimulationSystems::ReceiveUpdates),
+    );
+}
+
+/// Controls whether the client tries to resume its previous session when it receives a reset on a new connection.
+///
+/// If the server accepts, the [`TemplateWorld`] is kept and only what changed is resent.
+/// Enabled by default.
+#[derive(Resource, Deref, DerefMut)]
+pub struct ResumeSessions(pub bool);
+
+impl Default for ResumeSessions {
+    fn default() -> Self {
+        ResumeSessions(true)
+    }
+}
+
+/// The current prediction session of the client.
+#[derive(Resource, Default)]
+pub(crate) struct ClientSession {
+    token: Option<SessionToken>,
+    connection_entity: Option<Entity>,
+    pending_resume: Option<ResumeSession>,
+}
+
+/// Called when a reset is received from the server.
+///
+/// Returns `true` if the client will try to resume its previous session instead of resetting.
+pub(crate) fn try_resume<S>(
+    world: &mut World,
+    connection_entity: Entity,
+    simulation_tick: SimulationTick,
+    session: SessionToken,
+) -> bool
+where
+    S: PredictionScheme,
+{
+    let resume_sessions = **world.resource::<ResumeSessions>();
+
+    let mut client_session = world.resource_mut::<ClientSession>();
+    let previous_session = client_session.token.replace(session);
+    let previous_connection = client_session.connection_entity.replace(connection_entity);
+
+    let Some(previous_session) = previous_session else {
+        return false;
+    };
+
+    // A reset on the same connection is a deliberate reset by the server.
+    if !resume_sessions || previous_connection == Some(connection_entity) {
+        return false;
+    }
+
+    let template_tick = world
+        .resource::<TemplateWorld>()
+        .resource::<Time<SimulationTime>>()
+        .current_tick();
+
+    if template_tick > simulation_tick {
+        return false;
+    }
+
+    debug!(
+        "requesting to resume session {:?} from {:?}",
+        previous_session, template_tick
+    );
+
+    world.resource_mut::<ClientSession>().pending_resume = Some(ResumeSession {
+        previous_session,
+        tick: template_tick,
+    });
+
+    // Samples from the previous connection are no longer valid.
+    let current_time = world.resource::<Time<PredictionClock>>().elapsed();
+    world
+        .resource_mut::<ServerTickSamples>()
+        .reset::<S>(current_time, simulation_tick);
+
+    true
+}
+
+fn send_resume_requests(
+    mut client_session: ResMut<ClientSession>,
+    mut messages: PredictionSender<ClientPredictionStream>,
+) -> Result {
+    let Some(connection_entity) = client_session.connection_entity else {
+        return Ok(());
+    };
+
+    let Some(resume) = client_session.pending_resume.take() else {
+        return Ok(());
+    };
+
+    messages.write(connection_entity, true, &resume)?;
+
+    Ok(())
+}
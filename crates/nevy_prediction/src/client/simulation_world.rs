@@ -1,18 +1,19 @@
 //! Contains logic for running schedules on a world asynchronously to the main app.
 
-use bevy::{app::PluginsState, prelude::*};
+use bevy::{app::PluginsState, platform::time::Instant, prelude::*};
 use tracing::info_span;
 
-use crate::common::{
-    scheme::PredictionScheme,
-    simulation::{
-        PrivateSimulationTimeExt, SimulationInstance, SimulationTick, SimulationTime, SourceWorld,
+use crate::{
+    common::simulation::{
+        LateWorldUpdate, PrivateSimulationTimeExt, SimulationInstance, SimulationTick,
+        SimulationTickRate, SimulationTime, SourceWorld,
         schedules::{ExtractSimulation, ResetSimulation, SimulationStartupMain},
     },
+    diagnostics::PredictionFrameStats,
 };
 
 /// A separate world for containing prediction logic
-#[derive(Deref, DerefMut)]
+#[derive(Default, Deref, DerefMut)]
 pub struct SimulationWorld(World);
 
 impl SimulationWorld {
@@ -39,12 +40,18 @@ impl SimulationWorld {
         self.run_schedule(Main);
     }
 
+    /// Drains the [`LateWorldUpdate`]s written by this world so that they can be forwarded to another world.
+    ///
+    /// Simulation worlds don't update their messages, so this needs to be called after running them.
+    pub fn drain_late_updates(&mut self) -> Vec<LateWorldUpdate> {
+        self.resource_mut::<Messages<LateWorldUpdate>>()
+            .drain()
+            .collect()
+    }
+
     /// Updates the [`SimulationTime`] runs the [`ResetSimulation`] schedule.
-    pub fn reset<S>(&mut self, tick: SimulationTick)
-    where
-        S: PredictionScheme,
-    {
-        self.insert_resource(Time::<SimulationTime>::from_tick::<S>(tick));
+    pub fn reset(&mut self, tick: SimulationTick, tick_rate: SimulationTickRate) {
+        self.insert_resource(Time::<SimulationTime>::from_tick(tick, tick_rate));
 
         info_span!(
             "ResetSimulation",
@@ -57,6 +64,8 @@ impl SimulationWorld {
 
     /// Extracts this [`SimulationWorld`] into another [`World`]
     pub fn extract(&mut self, target_world: &mut World) {
+        let start = Instant::now();
+
         let owned_world = std::mem::take(&mut self.0);
 
         // Extract the simulation time from the source world.
@@ -83,5 +92,9 @@ impl SimulationWorld {
 
         // Swap world back and replace scratch world.
         self.0 = owned_world;
+
+        if let Some(mut stats) = target_world.get_resource_mut::<PredictionFrameStats>() {
+            stats.extract_time += start.elapsed();
+        }
     }
 }
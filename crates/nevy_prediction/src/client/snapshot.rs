@@ -0,0 +1,100 @@
+//! Holds off prediction while the server sends an initial snapshot.
+//!
+//! See the [`snapshot`](crate::server::snapshot) module of the server.
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    prelude::*,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    client::{
+        ClientSimulationSystems, PredictionServerConnection,
+        recording::{RecordedMessage, SessionRecorder},
+        template_world::TemplateWorld,
+    },
+    common::{
+        SnapshotComplete,
+        clock::PredictionClock,
+        loopback::PredictionReceiver,
+        simulation::{SimulationTick, SimulationTime, SimulationTimeExt},
+    },
+};
+
+pub(crate) fn build(app: &mut App, schedule: Interned<dyn ScheduleLabel>) {
+    app.init_resource::<SnapshotStatus>();
+
+    app.add_systems(
+        schedule,
+        (
+            receive_snapshot_completions.in_set(ClientSimulationSystems::ReceiveUpdates),
+            update_snapshot_status
+                .after(ClientSimulationSystems::RunTemplateWorld)
+                .before(ClientSimulationSystems::QueuePredictionUpdates),
+        ),
+    );
+}
+
+/// The state of the initial snapshot sent by the server.
+///
+/// Prediction doesn't run until this is [`SnapshotStatus::Complete`].
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotStatus {
+    /// The snapshot has been applied to the [`TemplateWorld`], or the server doesn't send one.
+    #[default]
+    Complete,
+    /// The server is still sending the snapshot.
+    Awaiting,
+    /// The server has sent the whole snapshot, and it will be applied once the template world executes this tick.
+    Received(SimulationTick),
+}
+
+impl SnapshotStatus {
+    pub fn is_complete(&self) -> bool {
+        matches!(self, SnapshotStatus::Complete)
+    }
+}
+
+fn receive_snapshot_completions(
+    mut received: PredictionReceiver<SnapshotComplete, PredictionServerConnection>,
+    mut status: ResMut<SnapshotStatus>,
+    mut recorder: ResMut<SessionRecorder>,
+    clock: Res<Time<PredictionClock>>,
+) {
+    for (connection_entity, is_server, SnapshotComplete { simulation_tick }) in received.drain() {
+        if !is_server {
+            warn!(
+                "Received a prediction message from a connection that isn't the server: {}",
+                connection_entity
+            );
+
+            continue;
+        }
+
+        recorder.record(
+            clock.elapsed(),
+            RecordedMessage::SnapshotComplete { simulation_tick },
+        );
+
+        if let SnapshotStatus::Awaiting = *status {
+            *status = SnapshotStatus::Received(simulation_tick);
+        }
+    }
+}
+
+fn update_snapshot_status(mut status: ResMut<SnapshotStatus>, template_world: Res<TemplateWorld>) {
+    let SnapshotStatus::Received(tick) = *status else {
+        return;
+    };
+
+    let template_tick = template_world
+        .resource::<Time<SimulationTime>>()
+        .current_tick();
+
+    if template_tick > tick {
+        debug!("initial snapshot applied on {:?}", tick);
+
+        *status = SnapshotStatus::Complete;
+    }
+}
@@ -0,0 +1,86 @@
+//! Spectator clients show the [`TemplateWorld`] without running client side prediction.
+//!
+//! Spectators and replay viewers have no inputs, so predicting ahead of the server has no benefit.
+//! While the [`Spectator`] resource exists the prediction world doesn't run,
+//! and the template world is extracted into the main world whenever it executes a tick.
+//!
+//! The main world then follows the server's estimated tick instead of predicting ahead of it,
+//! optionally delayed by [`Spectator::delay`] ticks.
+//! The delay buffers ticks from the server so that the template world advances at a steady rate when they arrive unevenly.
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    prelude::*,
+};
+
+use crate::{
+    client::{
+        ClientSimulationSystems, prediction::PredictionUpdates, snapshot::SnapshotStatus,
+        template_world::TemplateWorld,
+    },
+    common::simulation::{SimulationTick, SimulationTime, SimulationTimeExt},
+};
+
+pub(crate) fn build(app: &mut App, schedule: Interned<dyn ScheduleLabel>) {
+    app.add_systems(
+        schedule,
+        extract_template_world
+            .in_set(ClientSimulationSystems::RunPredictionWorld)
+            .run_if(resource_exists::<Spectator>),
+    );
+}
+
+pub(crate) fn build_update<T>(app: &mut App, schedule: Interned<dyn ScheduleLabel>)
+where
+    T: Send + Sync + 'static,
+{
+    app.add_systems(
+        schedule,
+        discard_prediction_updates::<T>
+            .in_set(ClientSimulationSystems::QueuePredictionUpdates)
+            .run_if(resource_exists::<Spectator>),
+    );
+}
+
+/// Insert this resource to make the client a spectator.
+///
+/// See the [`spectator`](self) module.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct Spectator {
+    /// How many ticks the main world is behind the server's estimated tick.
+    pub delay: u32,
+}
+
+/// Extracts the [`TemplateWorld`] into the main world when it has executed a new tick.
+fn extract_template_world(
+    world: &mut World,
+    mut last_extracted_tick: Local<Option<SimulationTick>>,
+) {
+    // A partial snapshot would show entities popping in.
+    if !world.resource::<SnapshotStatus>().is_complete() {
+        return;
+    }
+
+    let template_tick = world
+        .resource::<TemplateWorld>()
+        .resource::<Time<SimulationTime>>()
+        .current_tick();
+
+    if *last_extracted_tick == Some(template_tick) {
+        return;
+    }
+
+    *last_extracted_tick = Some(template_tick);
+
+    world.resource_scope(|world, mut template_world: Mut<TemplateWorld>| {
+        template_world.extract(world);
+    });
+}
+
+/// Updates sent by the server for prediction aren't needed by spectators.
+fn discard_prediction_updates<T>(mut prediction_updates: ResMut<PredictionUpdates<T>>)
+where
+    T: Send + Sync + 'static,
+{
+    prediction_updates.clear();
+}
@@ -7,23 +7,30 @@ use std::{collections::VecDeque, marker::PhantomData, time::Duration};
 
 use bevy::{
     ecs::{intern::Interned, schedule::ScheduleLabel},
+    platform::time::Instant,
     prelude::*,
 };
-use nevy::prelude::*;
-use tracing::warn;
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::{debug, warn};
 
 use crate::{
     client::{
         ClientSimulationSystems, PredictionBudget, PredictionServerConnection,
-        prediction::{PredictionUpdates, PredictionWorld},
+        budget::SimulationTickCosts,
+        prediction::PredictionUpdates,
+        recording::{RecordedMessage, SessionRecorder},
         simulation_world::SimulationWorld,
+        spectator::Spectator,
     },
     common::{
-        ServerWorldUpdate, UpdateServerTick,
+        ServerWorldUpdate, UpdateServerTick, UpdateTimeScale,
+        clock::{PredictionClock, SimulationTimeScale},
+        loopback::PredictionReceiver,
         scheme::PredictionScheme,
         simulation::{
-            SimulationInstance, SimulationPlugin, SimulationTick, SimulationTime,
-            SimulationTimeExt, UpdateExecutionQueue,
+            LateWorldUpdate, SimulationInstance, SimulationPlugin, SimulationTick,
+            SimulationTickRate, SimulationTime, SimulationTimeExt, UpdateExecutionQueue,
+            WorldUpdate,
         },
     },
 };
@@ -38,7 +45,9 @@ where
     app.add_systems(
         schedule,
         (
-            receive_time_updates::<S>.in_set(ClientSimulationSystems::ReceiveUpdates),
+            (receive_time_updates::<S>, receive_time_scale_updates::<S>)
+                .chain()
+                .in_set(ClientSimulationSystems::ReceiveUpdates),
             run_template_world.in_set(ClientSimulationSystems::RunTemplateWorld),
         ),
     );
@@ -46,7 +55,7 @@ where
 
 pub(crate) fn build_update<T>(app: &mut App, schedule: Interned<dyn ScheduleLabel>)
 where
-    T: Send + Sync + 'static + Clone,
+    T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone,
 {
     app.add_systems(
         schedule,
@@ -85,41 +94,62 @@ impl TemplateWorld {
 
 fn receive_world_updates<T>(
     mut server_world: ResMut<TemplateWorld>,
-    mut message_q: Query<(
-        Entity,
-        &mut ReceivedMessages<ServerWorldUpdate<T>>,
-        Has<PredictionServerConnection>,
-    )>,
-    mut prediction_world: ResMut<PredictionWorld>,
-) where
-    T: Send + Sync + 'static + Clone,
+    mut received: PredictionReceiver<ServerWorldUpdate<T>, PredictionServerConnection>,
+    mut prediction_updates: ResMut<PredictionUpdates<T>>,
+    mut recorder: ResMut<SessionRecorder>,
+    clock: Res<Time<PredictionClock>>,
+) -> Result
+where
+    T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone,
 {
-    for (connection_entity, mut messages, is_server) in &mut message_q {
-        for ServerWorldUpdate {
+    for (
+        connection_entity,
+        is_server,
+        ServerWorldUpdate {
             update,
             include_in_prediction,
-        } in messages.drain()
-        {
-            if !is_server {
-                warn!(
-                    "Received a prediction message from a connection that isn't the server: {}",
-                    connection_entity
-                );
-
-                continue;
-            }
-
-            if include_in_prediction {
-                let mut prediction_updates =
-                    prediction_world.resource_mut::<PredictionUpdates<T>>();
-                prediction_updates.insert(update.clone());
-            }
-
-            server_world
-                .resource_mut::<UpdateExecutionQueue<T>>()
-                .insert(update);
+        },
+    ) in received.drain()
+    {
+        if !is_server {
+            warn!(
+                "Received a prediction message from a connection that isn't the server: {}",
+                connection_entity
+            );
+
+            continue;
         }
+
+        recorder.record_update(clock.elapsed(), include_in_prediction, &update)?;
+
+        queue_server_update(
+            &mut server_world,
+            &mut prediction_updates,
+            include_in_prediction,
+            update,
+        );
+    }
+
+    Ok(())
+}
+
+/// Queues a world update received from the server in the template world,
+/// and in the prediction world if `include_in_prediction` is set.
+pub(crate) fn queue_server_update<T>(
+    server_world: &mut TemplateWorld,
+    prediction_updates: &mut PredictionUpdates<T>,
+    include_in_prediction: bool,
+    update: WorldUpdate<T>,
+) where
+    T: Send + Sync + 'static + Clone,
+{
+    if include_in_prediction {
+        prediction_updates.insert(update.clone());
     }
+
+    server_world
+        .resource_mut::<UpdateExecutionQueue<T>>()
+        .insert(update);
 }
 
 /// Contains the most recent server time update.
@@ -132,13 +162,13 @@ pub struct ServerTickSamples {
 impl ServerTickSamples {
     const SERVER_TIME_ESTIMATE_SAMPLES: usize = 32;
 
-    pub fn push<S>(&mut self, real_time: Duration, tick: SimulationTick)
+    pub fn push<S>(&mut self, received_time: Duration, tick: SimulationTick)
     where
         S: PredictionScheme,
     {
         self.latest = tick;
 
-        self.samples.push_back((real_time, tick));
+        self.samples.push_back((received_time, tick));
 
         while self.samples.len() > Self::SERVER_TIME_ESTIMATE_SAMPLES {
             self.samples.pop_front();
@@ -158,15 +188,20 @@ impl ServerTickSamples {
         self.latest
     }
 
-    pub fn estimated_time<S>(&self, real_time: Duration) -> Duration
-    where
-        S: PredictionScheme,
-    {
+    /// Estimates the server's current simulation time from the received samples.
+    ///
+    /// `time_scale` is the [`SimulationTimeScale::effective`] scale of the server.
+    pub fn estimated_time(
+        &self,
+        tick_rate: SimulationTickRate,
+        time_scale: f32,
+        current_time: Duration,
+    ) -> Duration {
         self.samples
             .iter()
             .map(|&(received_time, sample)| {
-                let elapsed = real_time - received_time;
-                let sample_time = sample.time::<S>();
+                let elapsed = (current_time - received_time).mul_f32(time_scale);
+                let sample_time = tick_rate.tick_time(sample);
 
                 sample_time + elapsed
             })
@@ -178,33 +213,99 @@ impl ServerTickSamples {
 
 /// Responsible for receiving [`UpdateServerTick`]s.
 fn receive_time_updates<S>(
-    mut message_q: Query<&mut ReceivedMessages<UpdateServerTick>>,
+    mut received: PredictionReceiver<UpdateServerTick, PredictionServerConnection>,
     mut tick_samples: ResMut<ServerTickSamples>,
-    real_time: Res<Time<Real>>,
+    mut recorder: ResMut<SessionRecorder>,
+    clock: Res<Time<PredictionClock>>,
     // mut time: ResMut<Time<SimulationTime>>,
     // prediction_interval: Res<PredictionInterval>,
 ) -> Result
 where
     S: PredictionScheme,
 {
-    for mut messages in &mut message_q {
-        for UpdateServerTick { simulation_tick } in messages.drain() {
-            tick_samples.push::<S>(real_time.elapsed(), simulation_tick);
-        }
+    for (_, _, UpdateServerTick { simulation_tick }) in received.drain() {
+        recorder.record(
+            clock.elapsed(),
+            RecordedMessage::ServerTick { simulation_tick },
+        );
+
+        tick_samples.push::<S>(clock.elapsed(), simulation_tick);
     }
 
     Ok(())
 }
 
+/// Responsible for receiving [`UpdateTimeScale`]s.
+fn receive_time_scale_updates<S>(
+    mut received: PredictionReceiver<UpdateTimeScale, PredictionServerConnection>,
+    mut time_scale: ResMut<SimulationTimeScale>,
+    mut tick_samples: ResMut<ServerTickSamples>,
+    mut recorder: ResMut<SessionRecorder>,
+    clock: Res<Time<PredictionClock>>,
+) where
+    S: PredictionScheme,
+{
+    for (connection_entity, is_server, UpdateTimeScale { time_scale: update }) in received.drain() {
+        if !is_server {
+            warn!(
+                "Received a prediction message from a connection that isn't the server: {}",
+                connection_entity
+            );
+
+            continue;
+        }
+
+        recorder.record(
+            clock.elapsed(),
+            RecordedMessage::TimeScale { time_scale: update },
+        );
+
+        apply_time_scale::<S>(&mut time_scale, &mut tick_samples, clock.elapsed(), update);
+    }
+}
+
+/// Sets the [`SimulationTimeScale`] received from the server.
+///
+/// Samples received at a different time scale would give the wrong estimate of the server's time,
+/// so the samples are restarted from the latest tick.
+pub(crate) fn apply_time_scale<S>(
+    time_scale: &mut SimulationTimeScale,
+    tick_samples: &mut ServerTickSamples,
+    current_time: Duration,
+    update: SimulationTimeScale,
+) where
+    S: PredictionScheme,
+{
+    if *time_scale == update {
+        return;
+    }
+
+    debug!("server changed the time scale to {:?}", update);
+
+    *time_scale = update;
+
+    let latest = tick_samples.latest();
+    tick_samples.reset::<S>(current_time, latest);
+}
+
 fn run_template_world(
     mut budget: ResMut<PredictionBudget>,
+    mut costs: ResMut<SimulationTickCosts>,
     time: Res<ServerTickSamples>,
     mut template_world: ResMut<TemplateWorld>,
+    spectator: Option<Res<Spectator>>,
+    main_time: Res<Time<SimulationTime>>,
+    mut late_updates: MessageWriter<LateWorldUpdate>,
 ) {
     let current_tick = template_world
         .resource::<Time<SimulationTime>>()
         .current_tick();
-    let desired_tick = time.latest();
+    let mut desired_tick = time.latest();
+
+    // Spectators advance the template world with the main world, which is delayed behind the server.
+    if spectator.is_some() {
+        desired_tick = desired_tick.min(main_time.target_tick());
+    }
 
     let desired_ticks = *desired_tick - *current_tick;
 
@@ -215,5 +316,10 @@ fn run_template_world(
     }
 
     budget.template -= execute_ticks;
+
+    let start = Instant::now();
     template_world.run(execute_ticks);
+    costs.record_template(start.elapsed(), execute_ticks);
+
+    late_updates.write_batch(template_world.drain_late_updates());
 }
@@ -0,0 +1,108 @@
+//! The clock that drives simulation time on the client and server.
+//!
+//! By default simulation time follows [`Time<Real>`], but the [`PredictionTimeSource`] resource can make it follow
+//! [`Time<Virtual>`] instead, which can be paused and sped up, or only advance when [`ManualPredictionTime::advance`] is called.
+//!
+//! All prediction logic that depends on the passage of time reads [`Time<PredictionClock>`],
+//! which is updated from the time source in [`First`] after bevy's time systems.
+//!
+//! On top of the clock, the server can pause or slow down the simulation with the [`SimulationTimeScale`] resource,
+//! which is sent to clients so that they don't predict further ahead than the server will go.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeSystems};
+use serde::{Deserialize, Serialize};
+
+pub(crate) fn build(app: &mut App) {
+    app.init_resource::<PredictionTimeSource>();
+    app.init_resource::<ManualPredictionTime>();
+    app.init_resource::<Time<PredictionClock>>();
+    app.init_resource::<SimulationTimeScale>();
+
+    app.add_systems(First, update_prediction_clock.after(TimeSystems));
+}
+
+/// The time context of the clock used by prediction logic.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct PredictionClock;
+
+/// Which clock [`Time<PredictionClock>`] follows.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PredictionTimeSource {
+    /// Follows [`Time<Real>`].
+    #[default]
+    Real,
+    /// Follows [`Time<Virtual>`], so pausing or scaling virtual time also pauses or scales the simulation.
+    Virtual,
+    /// Only advances by the time passed to [`ManualPredictionTime::advance`].
+    Manual,
+}
+
+/// Time that [`Time<PredictionClock>`] will advance by on the next frame
+/// when the [`PredictionTimeSource`] is [`Manual`](PredictionTimeSource::Manual).
+#[derive(Resource, Default)]
+pub struct ManualPredictionTime {
+    pending: Duration,
+}
+
+impl ManualPredictionTime {
+    /// Advances the prediction clock by some amount on the next frame.
+    pub fn advance(&mut self, delta: Duration) {
+        self.pending += delta;
+    }
+}
+
+/// Controls how fast the simulation runs relative to the [`PredictionClock`].
+///
+/// Change this resource on the server to pause, resume or slow down the simulation.
+/// Changes are sent to every client, where this resource is read only.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SimulationTimeScale {
+    /// The speed of the simulation, where `1` is normal speed.
+    pub scale: f32,
+    pub paused: bool,
+}
+
+impl Default for SimulationTimeScale {
+    fn default() -> Self {
+        SimulationTimeScale {
+            scale: 1.,
+            paused: false,
+        }
+    }
+}
+
+impl SimulationTimeScale {
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// The scale that simulation time actually advances at, which is zero while paused.
+    pub fn effective(&self) -> f32 {
+        match self.paused {
+            true => 0.,
+            false => self.scale.max(0.),
+        }
+    }
+}
+
+fn update_prediction_clock(
+    source: Res<PredictionTimeSource>,
+    real_time: Res<Time<Real>>,
+    virtual_time: Res<Time<Virtual>>,
+    mut manual_time: ResMut<ManualPredictionTime>,
+    mut clock: ResMut<Time<PredictionClock>>,
+) {
+    let delta = match *source {
+        PredictionTimeSource::Real => real_time.delta(),
+        PredictionTimeSource::Virtual => virtual_time.delta(),
+        PredictionTimeSource::Manual => std::mem::take(&mut manual_time.pending),
+    };
+
+    clock.advance_by(delta);
+}
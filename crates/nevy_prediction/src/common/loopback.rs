@@ -0,0 +1,375 @@
+//! An in-memory transport that connects a server app and client apps without sockets.
+//!
+//! Create a [`LoopbackLink`] and call [`LoopbackLink::connect`] with the worlds of a
+//! [`NevyPredictionServerPlugin`](crate::server::NevyPredictionServerPlugin) app and a
+//! [`NevyPredictionClientPlugin`](crate::client::NevyPredictionClientPlugin) app.
+//! This spawns a connection entity in each world with a [`LoopbackConnection`],
+//! and every prediction message sent to that entity is serialized and delivered to the other world
+//! according to the link's [`LoopbackConditions`].
+//!
+//! Messages are delivered once the receiving app's [`Time<PredictionClock>`] reaches their delivery time,
+//! so the apps should use the same [`PredictionTimeSource`](crate::common::clock::PredictionTimeSource)
+//! and advance it by the same amounts for latency to be deterministic,
+//! e.g. with a manual time source or the same [`TimeUpdateStrategy`](bevy::time::TimeUpdateStrategy).
+//!
+//! Delivered messages of a type that is never received are discarded after [`LoopbackLink::UNREAD_EXPIRY`].
+//!
+//! The nevy plugins are still required for message registration, but no endpoints need to be opened.
+
+use std::{
+    any::TypeId,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use nevy::prelude::*;
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::error;
+
+use crate::{
+    client::PredictionServerConnection, common::clock::PredictionClock, server::PredictionClient,
+};
+
+/// The network conditions simulated by a [`LoopbackLink`].
+#[derive(Clone, Debug)]
+pub struct LoopbackConditions {
+    /// How long every message takes to be delivered.
+    pub latency: Duration,
+    /// A random extra delay between zero and this value is added to every message.
+    pub jitter: Duration,
+    /// The probability from `0` to `1` that a message is lost and has to be retransmitted.
+    pub loss: f32,
+    /// How long it takes for a lost message to be retransmitted.
+    pub retransmit_delay: Duration,
+    /// Whether messages can be delivered in a different order than they were sent.
+    ///
+    /// The crate's streams are reliable and ordered, so by default a delayed message also delays every message sent after it.
+    pub reordering: bool,
+    /// The seed used to randomize jitter and loss.
+    pub seed: u64,
+}
+
+impl Default for LoopbackConditions {
+    fn default() -> Self {
+        LoopbackConditions {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            loss: 0.,
+            retransmit_delay: Duration::from_millis(100),
+            reordering: false,
+            seed: 0,
+        }
+    }
+}
+
+/// Which end of a [`LoopbackLink`] a [`LoopbackConnection`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopbackSide {
+    Server,
+    Client,
+}
+
+impl LoopbackSide {
+    fn index(self) -> usize {
+        match self {
+            LoopbackSide::Server => 0,
+            LoopbackSide::Client => 1,
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            LoopbackSide::Server => LoopbackSide::Client,
+            LoopbackSide::Client => LoopbackSide::Server,
+        }
+    }
+}
+
+struct LoopbackPacket {
+    type_id: TypeId,
+    deliver_at: Duration,
+    bytes: Vec<u8>,
+}
+
+#[derive(Default)]
+struct LoopbackDirection {
+    packets: Vec<LoopbackPacket>,
+    last_delivery: Duration,
+}
+
+impl LoopbackDirection {
+    /// Discards packets that were delivered too long ago to still be received.
+    fn expire(&mut self, now: Duration) {
+        self.packets
+            .retain(|packet| packet.deliver_at + LoopbackLink::UNREAD_EXPIRY >= now);
+    }
+}
+
+struct LoopbackState {
+    conditions: LoopbackConditions,
+    rng: u64,
+    /// Messages that will be received by each side.
+    directions: [LoopbackDirection; 2],
+}
+
+impl LoopbackState {
+    fn next_f32(&mut self) -> f32 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// A shared in-memory link between a server and a client.
+///
+/// Cloning the link shares the same message queues.
+#[derive(Clone)]
+pub struct LoopbackLink(Arc<Mutex<LoopbackState>>);
+
+impl LoopbackLink {
+    /// How long a delivered message is kept if nothing receives its type.
+    pub const UNREAD_EXPIRY: Duration = Duration::from_secs(10);
+
+    pub fn new(conditions: LoopbackConditions) -> Self {
+        LoopbackLink(Arc::new(Mutex::new(LoopbackState {
+            rng: conditions.seed,
+            conditions,
+            directions: default(),
+        })))
+    }
+
+    /// Changes the conditions for messages sent after this call.
+    pub fn set_conditions(&self, conditions: LoopbackConditions) {
+        self.0.lock().unwrap().conditions = conditions;
+    }
+
+    /// Spawns a connected [`PredictionClient`] in the server world and [`PredictionServerConnection`] in the client world.
+    ///
+    /// Returns the server's entity for the client and the client's entity for the server.
+    pub fn connect(&self, server_world: &mut World, client_world: &mut World) -> (Entity, Entity) {
+        let client_entity = server_world
+            .spawn((
+                LoopbackConnection {
+                    link: self.clone(),
+                    side: LoopbackSide::Server,
+                },
+                PredictionClient,
+            ))
+            .id();
+
+        let server_entity = client_world
+            .spawn((
+                LoopbackConnection {
+                    link: self.clone(),
+                    side: LoopbackSide::Client,
+                },
+                PredictionServerConnection,
+            ))
+            .id();
+
+        (client_entity, server_entity)
+    }
+
+    /// Sends a message from one side of the link at the time `now` of the sending side's clock.
+    ///
+    /// This is called by the prediction plugins for [`LoopbackConnection`]s,
+    /// and can be called directly to test the link.
+    pub fn send<M>(&self, from: LoopbackSide, now: Duration, message: &M) -> Result
+    where
+        M: Serialize + 'static,
+    {
+        let bytes = bincode::serialize(message)?;
+
+        let mut state = self.0.lock().unwrap();
+
+        let jitter_roll = state.next_f32();
+        let loss_roll = state.next_f32();
+
+        let conditions = &state.conditions;
+        let mut delay = conditions.latency + conditions.jitter.mul_f32(jitter_roll);
+
+        if loss_roll < conditions.loss {
+            delay += conditions.retransmit_delay;
+        }
+
+        let reordering = conditions.reordering;
+        let direction = &mut state.directions[from.other().index()];
+
+        direction.expire(now);
+
+        let mut deliver_at = now + delay;
+
+        if !reordering {
+            deliver_at = deliver_at.max(direction.last_delivery);
+            direction.last_delivery = deliver_at;
+        }
+
+        direction.packets.push(LoopbackPacket {
+            type_id: TypeId::of::<M>(),
+            deliver_at,
+            bytes,
+        });
+
+        Ok(())
+    }
+
+    /// Receives every message of type `M` that has been delivered to a side of the link by the time `now`,
+    /// in the order they were delivered.
+    pub fn receive<M>(&self, to: LoopbackSide, now: Duration) -> Vec<Result<M>>
+    where
+        M: DeserializeOwned + 'static,
+    {
+        let mut state = self.0.lock().unwrap();
+        let direction = &mut state.directions[to.index()];
+
+        direction.expire(now);
+
+        let mut delivered: Vec<LoopbackPacket> = Vec::new();
+
+        direction.packets.retain_mut(|packet| {
+            if packet.type_id != TypeId::of::<M>() || packet.deliver_at > now {
+                return true;
+            }
+
+            delivered.push(LoopbackPacket {
+                type_id: packet.type_id,
+                deliver_at: packet.deliver_at,
+                bytes: std::mem::take(&mut packet.bytes),
+            });
+
+            false
+        });
+
+        delivered.sort_by_key(|packet| packet.deliver_at);
+
+        delivered
+            .into_iter()
+            .map(|packet| Ok(bincode::deserialize(&packet.bytes)?))
+            .collect()
+    }
+}
+
+/// A connection entity that sends and receives prediction messages over a [`LoopbackLink`].
+#[derive(Component, Clone)]
+pub struct LoopbackConnection {
+    pub link: LoopbackLink,
+    pub side: LoopbackSide,
+}
+
+/// Sends prediction messages over [`LoopbackConnection`]s.
+#[derive(SystemParam)]
+pub(crate) struct LoopbackSender<'w, 's> {
+    loopback_q: Query<'w, 's, &'static LoopbackConnection>,
+    clock: Res<'w, Time<PredictionClock>>,
+}
+
+impl<'w, 's> LoopbackSender<'w, 's> {
+    /// Sends a message if the connection is a [`LoopbackConnection`].
+    ///
+    /// Returns `Ok(false)` if it isn't, and the message should be sent over nevy instead.
+    pub fn write<M>(&self, connection_entity: Entity, message: &M) -> Result<bool>
+    where
+        M: Serialize + 'static,
+    {
+        let Ok(loopback) = self.loopback_q.get(connection_entity) else {
+            return Ok(false);
+        };
+
+        loopback
+            .link
+            .send(loopback.side, self.clock.elapsed(), message)?;
+
+        Ok(true)
+    }
+}
+
+/// Sends prediction messages over nevy, or over a [`LoopbackLink`] for loopback connections.
+#[derive(SystemParam)]
+pub(crate) struct PredictionSender<'w, 's, S>
+where
+    S: Send + Sync + 'static,
+{
+    sender: SharedMessageSender<'w, 's, S>,
+    loopback: LoopbackSender<'w, 's>,
+}
+
+impl<'w, 's, S> PredictionSender<'w, 's, S>
+where
+    S: Send + Sync + 'static,
+{
+    /// See [`SharedMessageSender::write`].
+    pub fn write<M>(&mut self, connection_entity: Entity, queue: bool, message: &M) -> Result<bool>
+    where
+        M: Serialize + Send + Sync + 'static,
+    {
+        if self.loopback.write(connection_entity, message)? {
+            return Ok(true);
+        }
+
+        self.sender.write(connection_entity, queue, message)
+    }
+}
+
+type ReceiverQueryData<T, M> = (
+    Entity,
+    Option<&'static mut ReceivedMessages<T>>,
+    Option<&'static LoopbackConnection>,
+    Has<M>,
+);
+
+type ReceiverQueryFilter<T> = Or<(With<ReceivedMessages<T>>, With<LoopbackConnection>)>;
+
+/// Receives prediction messages from nevy connections and [`LoopbackConnection`]s.
+#[derive(SystemParam)]
+pub(crate) struct PredictionReceiver<'w, 's, T, M>
+where
+    T: Send + Sync + 'static,
+    M: Component,
+{
+    message_q: Query<'w, 's, ReceiverQueryData<T, M>, ReceiverQueryFilter<T>>,
+    clock: Res<'w, Time<PredictionClock>>,
+}
+
+impl<'w, 's, T, M> PredictionReceiver<'w, 's, T, M>
+where
+    T: Send + Sync + 'static + DeserializeOwned,
+    M: Component,
+{
+    /// Drains every received message, along with the connection it came from
+    /// and whether that connection has the marker component `M`.
+    pub fn drain(&mut self) -> Vec<(Entity, bool, T)> {
+        let now = self.clock.elapsed();
+        let mut received = Vec::new();
+
+        for (connection_entity, messages, loopback, is_marked) in &mut self.message_q {
+            if let Some(mut messages) = messages {
+                for message in messages.drain() {
+                    received.push((connection_entity, is_marked, message));
+                }
+            }
+
+            let Some(loopback) = loopback else {
+                continue;
+            };
+
+            for message in loopback.link.receive::<T>(loopback.side, now) {
+                match message {
+                    Ok(message) => received.push((connection_entity, is_marked, message)),
+                    Err(err) => error!(
+                        "Failed to deserialize a loopback message `{}`: {}",
+                        std::any::type_name::<T>(),
+                        err
+                    ),
+                }
+            }
+        }
+
+        received
+    }
+}
@@ -1,9 +1,20 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 use nevy::prelude::*;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-use crate::common::simulation::{SimulationTick, WorldUpdate, schedules::SimulationStartupMain};
+use crate::common::{
+    clock::SimulationTimeScale,
+    scheme::SynchronizationMode,
+    simulation::{
+        SimulationTick, SimulationTickRate, WorldUpdate, checksum::TickChecksums,
+        registry::ProtocolFingerprint, resync::ResyncTarget, schedules::SimulationStartupMain,
+    },
+};
 
+pub mod clock;
+pub mod loopback;
 pub mod scheme;
 pub mod simulation;
 
@@ -15,6 +26,19 @@ pub(crate) fn build(app: &mut App) {
 
     app.add_protocol_message::<PredictionMessages, ResetClientSimulation>();
     app.add_protocol_message::<PredictionMessages, UpdateServerTick>();
+    app.add_protocol_message::<PredictionMessages, UpdateTimeScale>();
+    app.add_protocol_message::<PredictionMessages, ServerSimulationChecksum>();
+    app.add_protocol_message::<PredictionMessages, ResyncRequest>();
+    app.add_protocol_message::<PredictionMessages, ResumeSession>();
+    app.add_protocol_message::<PredictionMessages, ResumeSessionResult>();
+    app.add_protocol_message::<PredictionMessages, SnapshotComplete>();
+    app.add_protocol_message::<PredictionMessages, ClientProtocolFingerprint>();
+    app.add_protocol_message::<PredictionMessages, PredictionPing>();
+    app.add_protocol_message::<PredictionMessages, PredictionPong>();
+
+    app.add_message::<ProtocolMismatch>();
+
+    clock::build(app);
 
     app.add_systems(PreStartup, startup_simulation);
 }
@@ -27,15 +51,88 @@ where
     app.add_protocol_message::<PredictionMessages, ServerWorldUpdate<T>>();
 }
 
+/// Build function run for the client and server app per world update that clients can create
+pub(crate) fn build_client_update<T>(app: &mut App)
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    if let SynchronizationMode::Lockstep = app.world().resource::<SynchronizationMode>() {
+        app.add_protocol_message::<PredictionMessages, ClientWorldUpdate<T>>();
+    }
+}
+
 /// Runs the startup schedules for all simulations
-fn startup_simulation(world: &mut World) {
+pub(crate) fn startup_simulation(world: &mut World) {
     world.run_schedule(SimulationStartupMain);
 }
 
 /// Server -> Client message to reset the simulation.
+///
+/// Also starts a new prediction session.
 #[derive(Serialize, Deserialize)]
 pub(crate) struct ResetClientSimulation {
     pub simulation_tick: SimulationTick,
+    pub session: SessionToken,
+    /// Whether the server will send an initial snapshot, see [`SnapshotComplete`].
+    pub snapshot: bool,
+    /// The server's [`ProtocolFingerprint`], which the client checks against its own.
+    pub fingerprint: ProtocolFingerprint,
+    pub tick_rate: SimulationTickRate,
+}
+
+/// Client -> Server message sent in response to every [`ResetClientSimulation`],
+/// so that the server can check that the client's scheme matches its own.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ClientProtocolFingerprint {
+    pub fingerprint: ProtocolFingerprint,
+}
+
+/// Written on the client or server when the other side's [`ProtocolFingerprint`] doesn't match.
+///
+/// The client ignores the reset from the server, and the server removes
+/// [`PredictionClient`](crate::server::PredictionClient) from the client so that it stops receiving world updates.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct ProtocolMismatch {
+    /// The connection to the other side.
+    pub connection_entity: Entity,
+    pub local: ProtocolFingerprint,
+    pub remote: ProtocolFingerprint,
+}
+
+/// Identifies a prediction session between a client and the server.
+///
+/// A client that reconnects can present the token of its previous session to resume it instead of resetting.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SessionToken(pub u64);
+
+/// Client -> Server message sent in response to a [`ResetClientSimulation`] on a new connection,
+/// asking to resume a previous session instead of resetting.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ResumeSession {
+    pub previous_session: SessionToken,
+    /// The next tick that the client's template world will execute.
+    pub tick: SimulationTick,
+}
+
+/// Server -> Client message in response to a [`ResumeSession`].
+///
+/// If the session wasn't resumed the client should reset its simulation to `simulation_tick`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ResumeSessionResult {
+    pub accepted: bool,
+    pub simulation_tick: SimulationTick,
+    /// Whether the server will send an initial snapshot if the session wasn't resumed.
+    pub snapshot: bool,
+    pub tick_rate: SimulationTickRate,
+}
+
+/// Server -> Client message sent once the last chunk of an initial snapshot has been sent.
+///
+/// See the [`snapshot`](crate::server::snapshot) module.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SnapshotComplete {
+    /// The tick that the last chunk of the snapshot is applied on.
+    pub simulation_tick: SimulationTick,
 }
 
 /// Server -> Client message to update the current simulation time on the server.
@@ -47,6 +144,41 @@ pub(crate) struct UpdateServerTick {
     pub simulation_tick: SimulationTick,
 }
 
+/// Server -> Client message sent when the server's [`SimulationTimeScale`] changes,
+/// and to every new client.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct UpdateTimeScale {
+    pub time_scale: SimulationTimeScale,
+}
+
+/// Client -> Server message used to measure the round trip time,
+/// see the [`diagnostics`](crate::diagnostics) module.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PredictionPing {
+    /// The client's [`Time<Real>`] when the ping was sent.
+    pub sent: Duration,
+}
+
+/// Server -> Client message sent in response to a [`PredictionPing`].
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PredictionPong {
+    pub sent: Duration,
+}
+
+/// Server -> Client message containing the server's checksums for a tick.
+///
+/// See the [`checksum`](crate::common::simulation::checksum) module.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ServerSimulationChecksum(pub TickChecksums);
+
+/// Client -> Server message to request a resync of part of the simulation.
+///
+/// See the [`resync`](crate::common::simulation::resync) module.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ResyncRequest {
+    pub target: ResyncTarget,
+}
+
 /// Server -> Client message to apply a [`WorldUpdate`].
 ///
 /// This type is in the public api only so that it's message id can be retrieved.
@@ -55,3 +187,11 @@ pub struct ServerWorldUpdate<T> {
     pub(crate) update: WorldUpdate<T>,
     pub(crate) include_in_prediction: bool,
 }
+
+/// Client -> Server message containing a [`WorldUpdate`] created by the client.
+///
+/// Only used in [`SynchronizationMode::Lockstep`].
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ClientWorldUpdate<T> {
+    pub update: WorldUpdate<T>,
+}
@@ -8,13 +8,56 @@ use crate::common::simulation::SimulationInstance;
 /// This trait defines a prediction scheme that controls how the client and server interact.
 ///
 /// Implement this trait on a marker type to define a prediction scheme.
+///
+/// Only one prediction scheme can be added to an app, once,
+/// with one of the server, client, offline or replay prediction plugins.
+/// Resources like [`Time<SimulationTime>`](crate::common::simulation::SimulationTime), the simulation entity map,
+/// the template and prediction worlds and the simulation schedules are not namespaced by scheme,
+/// so adding a second scheme, or the same scheme twice, panics.
+/// Independent simulations should be combined into a single scheme.
 pub trait PredictionScheme: Send + Sync + 'static {
     /// The plugin that should be added to any app that runs the simulation.
     fn plugin() -> impl Plugin;
 
+    /// The interval between simulation ticks when the simulation starts.
+    ///
+    /// The server can change it at runtime with a [`ChangeTickRate`](crate::server::ChangeTickRate) message.
     fn step_interval() -> Duration {
         Duration::from_millis(50)
     }
+
+    /// Controls how the server keeps clients in sync with its simulation.
+    fn synchronization_mode() -> SynchronizationMode {
+        SynchronizationMode::State
+    }
+}
+
+/// How the server keeps clients in sync with its simulation.
+///
+/// This is inserted as a resource into every instance of the simulation.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SynchronizationMode {
+    /// The server decides which world updates to send to clients with the [`WorldUpdateSender`](crate::server::WorldUpdateSender).
+    /// This can include state, and clients are responsible for sending their own requests to the server.
+    #[default]
+    State,
+    /// Pure input synchronization.
+    ///
+    /// World updates added with [`AddWorldUpdate::add_client_world_update`] that are created with a
+    /// [`PredictionUpdateCreator`](crate::client::PredictionUpdateCreator) are sent to the server automatically.
+    /// The server applies them and relays them to all clients with their tick, along with the usual tick updates.
+    /// Other world update types, including the crate's own, can only come from the server.
+    /// Clients stay in sync purely through determinism, so the simulation must be fully deterministic.
+    ///
+    /// The server never sends state by itself in this mode, but you can still use the [`WorldUpdateSender`](crate::server::WorldUpdateSender)
+    /// for server authored updates, or request a resync when a desync is detected.
+    ///
+    /// Updates that arrive at the server after their tick has executed are rejected,
+    /// because the client that created them has already predicted them on that tick.
+    /// Updates further ahead of the server than its [`MaxClientUpdateLead`](crate::server::lockstep::MaxClientUpdateLead) are also rejected.
+    /// The rejected update is removed from the client's prediction once its template world passes the update's tick.
+    /// Use a large enough [`PredictionInterval`](crate::client::PredictionInterval) so that updates arrive in time.
+    Lockstep,
 }
 
 pub trait AddWorldUpdate {
@@ -26,6 +69,14 @@ pub trait AddWorldUpdate {
     fn add_world_update<T>(&mut self) -> &mut Self
     where
         T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone;
+
+    /// Adds a simulation world update that clients are allowed to create.
+    ///
+    /// In [`SynchronizationMode::Lockstep`] only these updates are sent to the server by clients and relayed to every client.
+    /// In [`SynchronizationMode::State`] this is the same as [`add_world_update`](AddWorldUpdate::add_world_update).
+    fn add_client_world_update<T>(&mut self) -> &mut Self
+    where
+        T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone;
 }
 
 impl AddWorldUpdate for App {
@@ -39,6 +90,7 @@ impl AddWorldUpdate for App {
             SimulationInstance::Server => {
                 crate::common::build_update::<T>(self);
                 crate::common::simulation::build_update::<T>(self);
+                crate::server::build_update::<T>(self);
             }
             SimulationInstance::ClientMain => {
                 crate::client::build_update::<T>(self);
@@ -51,6 +103,32 @@ impl AddWorldUpdate for App {
             SimulationInstance::ClientPrediction => {
                 crate::common::simulation::build_update::<T>(self);
             }
+            SimulationInstance::Replay | SimulationInstance::Room | SimulationInstance::Offline => {
+                crate::common::simulation::build_update::<T>(self);
+            }
+        }
+
+        self
+    }
+
+    fn add_client_world_update<T>(&mut self) -> &mut Self
+    where
+        T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone,
+    {
+        self.add_world_update::<T>();
+
+        let instance = self.world().resource::<SimulationInstance>();
+
+        match instance {
+            SimulationInstance::Server => {
+                crate::common::build_client_update::<T>(self);
+                crate::server::lockstep::build_update::<T>(self);
+            }
+            SimulationInstance::ClientMain => {
+                crate::common::build_client_update::<T>(self);
+                crate::client::lockstep::build_update::<T>(self);
+            }
+            _ => {}
         }
 
         self
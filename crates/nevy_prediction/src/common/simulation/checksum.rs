@@ -0,0 +1,296 @@
+//! Opt-in desync detection using per-tick checksums of the simulation state.
+//!
+//! Components and resources registered with [`ChecksumComponentPlugin`] and [`ChecksumResourcePlugin`]
+//! are hashed during the [`SimulationChecksum`] schedule, which runs after [`SimulationPostUpdate`](crate::common::simulation::schedules::SimulationPostUpdate).
+//!
+//! The server sends its checksums to clients every [`ChecksumInterval`] ticks.
+//! Clients compare them against the checksums of their [`TemplateWorld`](crate::client::template_world::TemplateWorld)
+//! and write a [`SimulationDesync`](crate::client::desync::SimulationDesync) message if they don't match.
+//!
+//! Checksums are only computed by the server and the client's template world.
+//! Registered types must implement [`Hash`] in a way that is identical across instances,
+//! for floats this usually means hashing [`f32::to_bits`].
+
+use std::{
+    collections::VecDeque,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::common::simulation::{
+    SimulationInstance, SimulationTick, SimulationTime, SimulationTimeExt,
+    schedules::{ResetSimulation, SimulationChecksum},
+    simulation_entity::SimulationEntity,
+};
+
+pub(crate) fn build(app: &mut App) {
+    app.init_resource::<ChecksumRegistry>();
+    app.init_resource::<SimulationChecksums>();
+    app.init_resource::<ChecksumInterval>();
+
+    app.configure_sets(
+        SimulationChecksum,
+        (ChecksumSystems::Hash, ChecksumSystems::Record).chain(),
+    );
+
+    app.add_systems(
+        SimulationChecksum,
+        record_checksums.in_set(ChecksumSystems::Record),
+    );
+
+    app.add_systems(ResetSimulation, reset_checksums);
+}
+
+/// System sets in the [`SimulationChecksum`] schedule.
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChecksumSystems {
+    /// Where registered components and resources are hashed.
+    Hash,
+    /// Where the checksums of the tick are recorded into [`SimulationChecksums`].
+    Record,
+}
+
+/// How often the server sends its checksums to clients, in ticks.
+///
+/// A value of zero disables sending checksums.
+#[derive(Resource, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct ChecksumInterval(pub u32);
+
+impl Default for ChecksumInterval {
+    fn default() -> Self {
+        ChecksumInterval(30)
+    }
+}
+
+/// The ordered list of types that are included in checksums.
+///
+/// Like world updates, types must be registered in the same order on every instance of the simulation.
+#[derive(Resource, Default, Deref)]
+pub struct ChecksumRegistry(Vec<&'static str>);
+
+impl ChecksumRegistry {
+    fn register<T>(&mut self) {
+        self.0.push(std::any::type_name::<T>());
+    }
+
+    fn index_of<T>(&self) -> Option<usize> {
+        let type_name = std::any::type_name::<T>();
+        self.0.iter().position(|&name| name == type_name)
+    }
+}
+
+/// The checksums of every registered type for a single tick.
+///
+/// `checksums` is in the same order as the [`ChecksumRegistry`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickChecksums {
+    pub tick: SimulationTick,
+    pub checksums: Vec<u64>,
+}
+
+/// Holds the checksums of recently executed ticks.
+#[derive(Resource, Default)]
+pub struct SimulationChecksums {
+    current: Vec<u64>,
+    history: VecDeque<TickChecksums>,
+}
+
+impl SimulationChecksums {
+    const HISTORY_LENGTH: usize = 256;
+
+    /// Gets the checksums of a tick if they are still in the history.
+    pub fn get(&self, tick: SimulationTick) -> Option<&TickChecksums> {
+        self.history
+            .iter()
+            .rev()
+            .find(|checksums| checksums.tick == tick)
+    }
+
+    /// Gets the checksums of the most recently executed tick.
+    pub fn latest(&self) -> Option<&TickChecksums> {
+        self.history.back()
+    }
+
+    fn set(&mut self, index: usize, checksum: u64) {
+        if self.current.len() <= index {
+            self.current.resize(index + 1, 0);
+        }
+
+        self.current[index] = checksum;
+    }
+}
+
+/// FNV-1a hasher used for checksums.
+///
+/// Unlike the hashers in the standard library this is guaranteed to produce the same output in every process.
+pub struct ChecksumHasher(u64);
+
+impl Default for ChecksumHasher {
+    fn default() -> Self {
+        ChecksumHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for ChecksumHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        // Hash as a `u64` so that checksums are the same on 32 and 64 bit platforms.
+        self.write_u64(i as u64);
+    }
+}
+
+fn checksum_instance(app: &App) -> bool {
+    matches!(
+        app.world().resource::<SimulationInstance>(),
+        SimulationInstance::Server
+            | SimulationInstance::ClientTemplate
+            | SimulationInstance::Replay
+    )
+}
+
+/// Includes a component on [`SimulationEntity`]s in the simulation checksums.
+pub struct ChecksumComponentPlugin<C>(PhantomData<C>);
+
+impl<C> Default for ChecksumComponentPlugin<C> {
+    fn default() -> Self {
+        ChecksumComponentPlugin(PhantomData)
+    }
+}
+
+impl<C> Plugin for ChecksumComponentPlugin<C>
+where
+    C: Component + Hash,
+{
+    fn build(&self, app: &mut App) {
+        app.world_mut()
+            .resource_mut::<ChecksumRegistry>()
+            .register::<C>();
+
+        if checksum_instance(app) {
+            app.add_systems(
+                SimulationChecksum,
+                hash_component::<C>.in_set(ChecksumSystems::Hash),
+            );
+        }
+    }
+}
+
+/// Includes a resource in the simulation checksums.
+pub struct ChecksumResourcePlugin<R>(PhantomData<R>);
+
+impl<R> Default for ChecksumResourcePlugin<R> {
+    fn default() -> Self {
+        ChecksumResourcePlugin(PhantomData)
+    }
+}
+
+impl<R> Plugin for ChecksumResourcePlugin<R>
+where
+    R: Resource + Hash,
+{
+    fn build(&self, app: &mut App) {
+        app.world_mut()
+            .resource_mut::<ChecksumRegistry>()
+            .register::<R>();
+
+        if checksum_instance(app) {
+            app.add_systems(
+                SimulationChecksum,
+                hash_resource::<R>.in_set(ChecksumSystems::Hash),
+            );
+        }
+    }
+}
+
+fn hash_component<C>(
+    registry: Res<ChecksumRegistry>,
+    mut checksums: ResMut<SimulationChecksums>,
+    component_q: Query<(&SimulationEntity, &C)>,
+) -> Result
+where
+    C: Component + Hash,
+{
+    let index = registry.index_of::<C>().ok_or(format!(
+        "`{}` should be registered for checksums",
+        std::any::type_name::<C>()
+    ))?;
+
+    // Entity iteration order isn't the same across instances, so sort by simulation entity.
+    let mut entity_hashes: Vec<(u64, u64)> = component_q
+        .iter()
+        .map(|(simulation_entity, component)| {
+            let mut hasher = ChecksumHasher::default();
+            component.hash(&mut hasher);
+            (simulation_entity.0, hasher.finish())
+        })
+        .collect();
+
+    entity_hashes.sort_unstable();
+
+    let mut hasher = ChecksumHasher::default();
+    entity_hashes.hash(&mut hasher);
+
+    checksums.set(index, hasher.finish());
+
+    Ok(())
+}
+
+fn hash_resource<R>(
+    registry: Res<ChecksumRegistry>,
+    mut checksums: ResMut<SimulationChecksums>,
+    resource: Option<Res<R>>,
+) -> Result
+where
+    R: Resource + Hash,
+{
+    let index = registry.index_of::<R>().ok_or(format!(
+        "`{}` should be registered for checksums",
+        std::any::type_name::<R>()
+    ))?;
+
+    let mut hasher = ChecksumHasher::default();
+    resource.as_deref().hash(&mut hasher);
+
+    checksums.set(index, hasher.finish());
+
+    Ok(())
+}
+
+fn record_checksums(
+    registry: Res<ChecksumRegistry>,
+    mut checksums: ResMut<SimulationChecksums>,
+    time: Res<Time<SimulationTime>>,
+) {
+    if registry.is_empty() {
+        return;
+    }
+
+    let mut current = std::mem::take(&mut checksums.current);
+    current.resize(registry.len(), 0);
+
+    checksums.history.push_back(TickChecksums {
+        tick: time.current_tick(),
+        checksums: current,
+    });
+
+    while checksums.history.len() > SimulationChecksums::HISTORY_LENGTH {
+        checksums.history.pop_front();
+    }
+}
+
+fn reset_checksums(mut checksums: ResMut<SimulationChecksums>) {
+    *checksums = default();
+}
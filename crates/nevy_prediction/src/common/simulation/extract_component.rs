@@ -3,7 +3,7 @@ use std::marker::PhantomData;
 use bevy::{ecs::component::Mutable, prelude::*};
 
 use crate::common::simulation::{
-    ExtractSimulationSystems, SourceWorld,
+    ExtractSimulationSystems, SourceQueryCache, SourceWorld, registry,
     schedules::ExtractSimulation,
     simulation_entity::{SimulationEntity, SimulationEntityMap},
 };
@@ -76,6 +76,8 @@ where
     C: Send + Sync + 'static + Component<Mutability = Mutable> + Clone,
 {
     fn build(&self, app: &mut App) {
+        registry::register_extracted_component::<C>(app);
+
         app.configure_sets(
             ExtractSimulation,
             ExtractComponentSystems::<C>::default()
@@ -93,13 +95,13 @@ fn extract_component<C>(
     mut commands: Commands,
     mut source_world: ResMut<SourceWorld>,
     map: Res<SimulationEntityMap>,
-    mut source_component_q: Local<Option<QueryState<(&SimulationEntity, &C)>>>,
+    mut source_component_q: Local<SourceQueryCache<(&SimulationEntity, &C)>>,
     mut local_component_q: Query<&mut C>,
 ) -> Result
 where
     C: Component<Mutability = Mutable> + Clone,
 {
-    let new_component_q = source_component_q.get_or_insert_with(|| source_world.query_filtered());
+    let new_component_q = source_world.cached_query(&mut source_component_q);
 
     for (&simulation_entity, source_component) in new_component_q.iter(&mut *source_world) {
         let local_entity = map.get(simulation_entity).ok_or(format!(
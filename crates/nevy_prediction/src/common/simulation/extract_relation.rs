@@ -1,9 +1,12 @@
 use std::marker::PhantomData;
 
-use bevy::{ecs::relationship::Relationship, prelude::*};
+use bevy::{
+    ecs::{entity_disabling::Disabled, query::Allow, relationship::Relationship},
+    prelude::*,
+};
 
 use crate::common::simulation::{
-    ExtractSimulationSystems, SourceWorld,
+    ExtractSimulationSystems, SourceQueryCache, SourceWorld, registry,
     schedules::ExtractSimulation,
     simulation_entity::{SimulationEntity, SimulationEntityMap},
 };
@@ -76,6 +79,8 @@ where
     C: Component + Relationship,
 {
     fn build(&self, app: &mut App) {
+        registry::register_extracted_relation::<C>(app);
+
         app.configure_sets(
             ExtractSimulation,
             ExtractRelationSystems::<C>::default()
@@ -94,14 +99,15 @@ fn extract_relation<C>(
     local_relation_q: Query<&C, With<SimulationEntity>>,
     mut source_world: ResMut<SourceWorld>,
     map: Res<SimulationEntityMap>,
-    mut source_relation_q: Local<Option<QueryState<(&SimulationEntity, Option<&C>)>>>,
-    mut source_target_q: Local<Option<QueryState<&SimulationEntity>>>,
+    mut source_relation_q: Local<SourceQueryCache<(&SimulationEntity, Option<&C>)>>,
+    // Targets may be outside of the predicted subset.
+    mut source_target_q: Local<SourceQueryCache<&SimulationEntity, Allow<Disabled>>>,
 ) -> Result
 where
     C: Component + Relationship,
 {
-    let source_relation_q = source_relation_q.get_or_insert_with(|| source_world.query_filtered());
-    let source_target_q = source_target_q.get_or_insert_with(|| source_world.query_filtered());
+    let source_relation_q = source_world.cached_query(&mut source_relation_q);
+    let source_target_q = source_world.cached_query(&mut source_target_q);
 
     for (&relation_simulation_entity, relation) in source_relation_q.iter(source_world.as_ref()) {
         let local_relation_entity = map.get(relation_simulation_entity).ok_or(format!(
@@ -2,7 +2,7 @@ use std::marker::PhantomData;
 
 use bevy::{ecs::component::Mutable, prelude::*};
 
-use crate::common::simulation::{SourceWorld, schedules::ExtractSimulation};
+use crate::common::simulation::{SourceWorld, registry, schedules::ExtractSimulation};
 
 /// This plugin acts as a utility to automatically extract a resource.
 pub struct ExtractSimulationResourcePlugin<R>(PhantomData<R>);
@@ -18,6 +18,8 @@ where
     R: Send + Sync + 'static + Resource + Clone + Component<Mutability = Mutable>,
 {
     fn build(&self, app: &mut App) {
+        registry::register_extracted_resource::<R>(app);
+
         app.add_systems(ExtractSimulation, extract_resource::<R>);
     }
 }
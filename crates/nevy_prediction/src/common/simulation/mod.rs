@@ -1,7 +1,8 @@
 //! This module contains logic that controls the execution of a simulation instance.
 //!
 //! It adds a [`SimulationTime`] clock which is the generic [`Time`] resource for the [`SimulationUpdate`] schedule.
-//! This schedule is run on a fixed timestep with [`SimulationTime`], and is advanced up to [`SimulationTimeTarget`].
+//! This schedule is run on a fixed timestep with [`SimulationTime`], and is advanced up to its target tick,
+//! see [`SimulationTimeExt::target_tick`].
 //!
 //! It also controls when [`WorldUpdate`]s are applied with a [`WorldUpdateQueue`].
 //!
@@ -11,24 +12,38 @@
 use std::{collections::VecDeque, marker::PhantomData, time::Duration};
 
 use bevy::{
-    ecs::{entity::MapEntities, intern::Interned, schedule::ScheduleLabel, system::SystemParam},
+    ecs::{
+        entity::MapEntities,
+        intern::Interned,
+        query::{QueryData, QueryFilter},
+        schedule::ScheduleLabel,
+        system::{Deferred, SystemBuffer, SystemMeta, SystemParam},
+        world::DeferredWorld,
+        world::WorldId,
+    },
+    platform::collections::HashMap,
     prelude::*,
 };
-use serde::{Deserialize, Serialize};
-use tracing::warn;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::{debug, warn};
 
 use crate::common::{
-    scheme::PredictionScheme,
+    scheme::{AddWorldUpdate, PredictionScheme},
     simulation::{
+        resync::ResyncSystems,
         schedules::{ExtractSimulation, SimulationMain, SimulationUpdate},
         simulation_entity::DespawnSimulationEntities,
         update_component::UpdateComponentSystems,
     },
 };
 
+pub mod checksum;
 pub mod extract_component;
 pub mod extract_relation;
 pub mod extract_resource;
+pub mod random;
+pub mod registry;
+pub mod resync;
 pub mod schedules;
 pub mod simulation_entity;
 pub mod update_component;
@@ -50,6 +65,33 @@ pub enum ExtractSimulationSystems {
 #[derive(Resource, Deref, DerefMut)]
 pub struct SourceWorld(pub World);
 
+/// A query state for a [`SourceWorld`] that is cached in a system's [`Local`], see [`SourceWorld::cached_query`].
+pub(crate) type SourceQueryCache<D, F = ()> = Option<(WorldId, QueryState<D, F>)>;
+
+impl SourceWorld {
+    /// Gets a query state that is cached in a system's [`Local`], recreating it if it was created for a different source world.
+    ///
+    /// The main world of a client extracts from both the template and prediction worlds when it predicts a
+    /// [`PredictionSubset`](crate::client::prediction::PredictionSubset).
+    pub(crate) fn cached_query<'a, D, F>(
+        &mut self,
+        cache: &'a mut SourceQueryCache<D, F>,
+    ) -> &'a mut QueryState<D, F>
+    where
+        D: QueryData,
+        F: QueryFilter,
+    {
+        let world_id = self.id();
+
+        if cache.as_ref().is_some_and(|(id, _)| *id != world_id) {
+            *cache = None;
+        }
+
+        let (_, query) = cache.get_or_insert_with(|| (world_id, self.query_filtered()));
+        query
+    }
+}
+
 #[derive(
     Clone,
     Copy,
@@ -78,6 +120,66 @@ pub struct SimulationTick(pub u32);
 pub struct SimulationTime {
     current_tick: SimulationTick,
     target_tick: SimulationTick,
+    tick_rate: SimulationTickRate,
+}
+
+/// The interval between simulation ticks.
+///
+/// This starts as [`PredictionScheme::step_interval`] and can be changed at runtime by the server
+/// with a [`TickRateUpdate`], so it also records which tick the current interval took effect on.
+///
+/// The default tick rate has a zero interval, use [`SimulationTickRate::new`] for the tick rate of a scheme.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulationTickRate {
+    pub interval: Duration,
+    /// The tick that the interval took effect on.
+    pub since_tick: SimulationTick,
+    /// The time of [`Self::since_tick`].
+    pub since_time: Duration,
+}
+
+impl SimulationTickRate {
+    /// The default tick rate of a prediction scheme.
+    pub fn new<S>() -> Self
+    where
+        S: PredictionScheme,
+    {
+        SimulationTickRate {
+            interval: S::step_interval(),
+            since_tick: SimulationTick(0),
+            since_time: Duration::ZERO,
+        }
+    }
+
+    /// Calculates the timestamp of a tick.
+    ///
+    /// Ticks before [`Self::since_tick`] are assumed to have run at the current interval.
+    pub fn tick_time(&self, tick: SimulationTick) -> Duration {
+        if tick >= self.since_tick {
+            self.since_time + self.interval * (*tick - *self.since_tick)
+        } else {
+            self.since_time
+                .saturating_sub(self.interval * (*self.since_tick - *tick))
+        }
+    }
+
+    /// Returns a tick rate that changes to a new interval on a tick.
+    fn changed(&self, tick: SimulationTick, interval: Duration) -> Self {
+        SimulationTickRate {
+            interval,
+            since_tick: tick,
+            since_time: self.tick_time(tick),
+        }
+    }
+}
+
+/// A world update that changes the [`SimulationTickRate`].
+///
+/// This world update is added to every prediction scheme after the scheme's own world updates.
+/// The server sends it to clients when a [`ChangeTickRate`](crate::server::ChangeTickRate) message is written.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct TickRateUpdate {
+    pub interval: Duration,
 }
 
 /// System set where [`SimulationUpdate`] is run.
@@ -94,6 +196,13 @@ pub enum SimulationInstance {
     ClientMain,
     ClientTemplate,
     ClientPrediction,
+    /// A server simulation replaying a [`ServerRecording`](crate::server::recording::ServerRecording) without networking.
+    Replay,
+    /// A server simulation in a [`SimulationRoom`](crate::server::rooms::SimulationRoom).
+    Room,
+    /// A local authoritative simulation without networking,
+    /// see [`NevyPredictionOfflinePlugin`](crate::offline::NevyPredictionOfflinePlugin).
+    Offline,
 }
 
 impl SimulationInstance {
@@ -103,10 +212,19 @@ impl SimulationInstance {
             SimulationInstance::ClientMain => "ClientMain",
             SimulationInstance::ClientTemplate => "ClientTemplate",
             SimulationInstance::ClientPrediction => "ClientPrediction",
+            SimulationInstance::Replay => "Replay",
+            SimulationInstance::Room => "Room",
+            SimulationInstance::Offline => "Offline",
         }
     }
 }
 
+/// The type name of the prediction scheme running in an app.
+///
+/// Used to catch a scheme being added twice or a second scheme being added, see [`PredictionScheme`].
+#[derive(Resource)]
+struct ActivePredictionScheme(&'static str);
+
 /// This plugin is added to all instances of the simulation.
 ///
 /// Controls the execution of the [SimulationUpdate] and [SimulationTime].
@@ -121,15 +239,46 @@ where
     S: PredictionScheme,
 {
     fn build(&self, app: &mut App) {
+        let scheme = std::any::type_name::<S>();
+
+        match app.world().get_resource() {
+            Some(&ActivePredictionScheme(existing)) if existing == scheme => panic!(
+                "Tried to add the prediction scheme `{}` to an app twice. \
+                Only one of the server, client, offline and replay prediction plugins can be added to an app.",
+                scheme
+            ),
+            Some(&ActivePredictionScheme(existing)) => panic!(
+                "Tried to add the prediction scheme `{}` to an app that already runs `{}`. \
+                The simulation's resources and schedules aren't namespaced by scheme, \
+                so only one prediction scheme can run in an app.",
+                scheme, existing
+            ),
+            None => (),
+        }
+
+        app.insert_resource(ActivePredictionScheme(scheme));
+
         schedules::build(app);
 
         app.insert_resource(self.instance);
+        app.insert_resource(S::synchronization_mode());
+        app.init_resource::<LateUpdateCounts>();
+        app.add_message::<LateWorldUpdate>();
 
+        registry::build(app);
         simulation_entity::build(app);
+        resync::build(app);
+        random::build(app);
+        checksum::build(app);
 
         app.configure_sets(
             SimulationUpdate,
-            (UpdateComponentSystems, DespawnSimulationEntities).chain(),
+            (
+                ResyncSystems,
+                UpdateComponentSystems,
+                DespawnSimulationEntities,
+            )
+                .chain(),
         );
 
         app.configure_sets(
@@ -145,29 +294,43 @@ where
         if app.world().get_resource::<Time>().is_none() {
             app.init_resource::<Time>();
         }
-        app.init_resource::<Time<SimulationTime>>();
+        app.insert_resource(Time::<SimulationTime>::from_tick(
+            SimulationTick(0),
+            SimulationTickRate::new::<S>(),
+        ));
 
         app.add_systems(
             self.schedule,
-            run_simulation_schedule::<S>.in_set(StepSimulationSystems),
+            run_simulation_schedule.in_set(StepSimulationSystems),
+        );
+
+        app.add_systems(
+            SimulationUpdate,
+            apply_tick_rate_updates.before(ResyncSystems),
         );
 
         app.add_plugins(S::plugin());
+
+        app.add_world_update::<TickRateUpdate>();
+    }
+
+    // Duplicates are caught in `build` with a clearer message.
+    fn is_unique(&self) -> bool {
+        false
     }
 }
 
 pub(crate) fn build_update<T>(app: &mut App)
 where
-    T: Send + Sync + 'static,
+    T: Send + Sync + 'static + DeserializeOwned,
 {
     app.init_resource::<UpdateExecutionQueue<T>>();
+
+    registry::build_update::<T>(app);
 }
 
 /// Advances [SimulationTime] and the [SimulationUpdate].
-fn run_simulation_schedule<S>(world: &mut World)
-where
-    S: PredictionScheme,
-{
+fn run_simulation_schedule(world: &mut World) {
     // Save the current generic time to replace it after overwriting it with `SimulationTime`.
     let old_time = world.resource::<Time>().clone();
 
@@ -183,14 +346,47 @@ where
         world.run_schedule(SimulationMain);
 
         // `SimulationTime` contains the timestamp of the *next* update, so we advance it after executing `SimulationUpdate`.
-        world.resource_mut::<Time<SimulationTime>>().step::<S>();
+        world.resource_mut::<Time<SimulationTime>>().step();
     }
 
     *world.resource_mut::<Time>() = old_time;
 }
 
+/// Applies [`TickRateUpdate`]s, which take effect from the next tick.
+///
+/// Updates to a zero interval are dropped, because the simulation could never advance past them.
+fn apply_tick_rate_updates(
+    mut params: ParamSet<(ReadyUpdates<TickRateUpdate>, ResMut<Time<SimulationTime>>)>,
+) {
+    // `ReadyUpdates` reads the simulation time, so the updates are drained before it is changed.
+    let intervals: Vec<_> = params
+        .p0()
+        .drain()
+        .map(|TickRateUpdate { interval }| interval)
+        .collect();
+
+    let mut time = params.p1();
+    let next_tick = SimulationTick(*time.current_tick() + 1);
+
+    for interval in intervals {
+        if interval.is_zero() {
+            warn!(
+                "Dropping a tick rate update to a zero interval on {:?}",
+                next_tick
+            );
+
+            continue;
+        }
+
+        time.set_tick_rate(next_tick, interval);
+    }
+}
+
 impl SimulationTick {
-    /// Calculates what timestamp the simulation should be at given a prediction scheme
+    /// Calculates what timestamp the simulation should be at given the default tick rate of a prediction scheme.
+    ///
+    /// This doesn't account for changes to the [`SimulationTickRate`] at runtime,
+    /// use [`SimulationTimeExt::tick_time`] for that.
     pub fn time<S>(self) -> Duration
     where
         S: PredictionScheme,
@@ -253,17 +449,20 @@ impl<T> Default for UpdateExecutionQueue<T> {
 /// This system parameter will return simulation updates from their [`WorldUpdateQueue`] that are ready to be applied.
 ///
 /// For every world update there should be a system in [`SimulationUpdate`] that calls [`ReadyUpdates::drain`] and applies the updates to the world.
+///
+/// Updates that are returned after their tick write a [`LateWorldUpdate`] message.
 #[derive(SystemParam)]
-pub struct ReadyUpdates<'w, T>
+pub struct ReadyUpdates<'w, 's, T>
 where
     T: Send + Sync + 'static,
 {
     instance: Res<'w, SimulationInstance>,
     updates: ResMut<'w, UpdateExecutionQueue<T>>,
     time: Res<'w, Time<SimulationTime>>,
+    late_updates: Deferred<'s, LateUpdateBuffer>,
 }
 
-impl<'w, T> ReadyUpdates<'w, T>
+impl<'w, 's, T> ReadyUpdates<'w, 's, T>
 where
     T: Send + Sync + 'static,
 {
@@ -275,12 +474,20 @@ where
             };
 
             if update.tick != self.time.current_tick() {
-                warn!(
+                let late_update = LateWorldUpdate {
+                    type_name: std::any::type_name::<T>(),
+                    instance: *self.instance,
+                    intended_tick: update.tick,
+                    applied_tick: self.time.current_tick(),
+                    lateness: (*self.time.current_tick()).saturating_sub(*update.tick),
+                };
+
+                debug!(
                     "Returned an update `{}` late by {} ticks in instance {:?}",
-                    std::any::type_name::<T>(),
-                    (*self.time.current_tick()).saturating_sub(*update.tick),
-                    *self.instance,
-                )
+                    late_update.type_name, late_update.lateness, late_update.instance,
+                );
+
+                self.late_updates.push(late_update);
             }
 
             Some(update.update)
@@ -288,34 +495,69 @@ where
     }
 }
 
+/// Counts how many world updates of each type were applied after their tick in this instance of the simulation.
+#[derive(Resource, Default, Debug, Deref)]
+pub struct LateUpdateCounts(HashMap<&'static str, u64>);
+
+/// Written when [`ReadyUpdates::drain`] returns a world update after the tick it was meant for.
+///
+/// Instances of the simulation that run in their own [`World`], such as the client's template and prediction worlds
+/// and server [`SimulationRoom`](crate::server::rooms::SimulationRoom)s,
+/// forward these messages to the app's world after they run, so they can all be read with a [`MessageReader`].
+#[derive(Message, Clone, Copy, Debug)]
+pub struct LateWorldUpdate {
+    /// The type name of the world update.
+    pub type_name: &'static str,
+    /// The instance of the simulation that applied the update.
+    pub instance: SimulationInstance,
+    /// The tick the update was meant to be applied on.
+    pub intended_tick: SimulationTick,
+    /// The tick the update was applied on.
+    pub applied_tick: SimulationTick,
+    /// How many ticks late the update was applied.
+    pub lateness: u32,
+}
+
+/// Late updates are recorded with a [`SystemBuffer`] so that a system can drain multiple [`ReadyUpdates`].
+#[derive(Default, Deref, DerefMut)]
+struct LateUpdateBuffer(Vec<LateWorldUpdate>);
+
+impl SystemBuffer for LateUpdateBuffer {
+    fn queue(&mut self, _system_meta: &SystemMeta, mut world: DeferredWorld) {
+        let mut counts = world.resource_mut::<LateUpdateCounts>();
+
+        for late_update in self.0.iter() {
+            *counts.0.entry(late_update.type_name).or_default() += 1;
+        }
+
+        world.write_message_batch(self.0.drain(..));
+    }
+}
+
 pub(crate) trait PrivateSimulationTimeExt {
-    fn from_tick<S>(tick: SimulationTick) -> Self
-    where
-        S: PredictionScheme;
+    fn from_tick(tick: SimulationTick, tick_rate: SimulationTickRate) -> Self;
 
     fn extract_time(&self, other: &mut Self);
 
-    fn step<S>(&mut self)
-    where
-        S: PredictionScheme;
+    fn step(&mut self);
 
     fn queue_ticks(&mut self, ticks: u32);
 
     fn clear_target(&mut self);
+
+    fn set_tick_rate(&mut self, tick: SimulationTick, interval: Duration);
 }
 
 impl PrivateSimulationTimeExt for Time<SimulationTime> {
-    fn from_tick<S>(tick: SimulationTick) -> Self
-    where
-        S: PredictionScheme,
-    {
+    fn from_tick(tick: SimulationTick, tick_rate: SimulationTickRate) -> Self {
         let mut time = Time::new_with(SimulationTime {
             current_tick: tick,
             target_tick: tick,
+            tick_rate,
         });
 
-        let target_time = time.current_tick().time::<S>();
-        time.advance_to(target_time.saturating_sub(S::step_interval())); // ensures delta is set correctly
+        let target_time = time.tick_time(tick);
+        time.advance_to(target_time.saturating_sub(tick_rate.interval)); // ensures delta is set correctly
         time.advance_to(target_time);
 
         time
@@ -328,12 +570,9 @@ impl PrivateSimulationTimeExt for Time<SimulationTime> {
         other.context_mut().target_tick = target_tick;
     }
 
-    fn step<S>(&mut self)
-    where
-        S: PredictionScheme,
-    {
+    fn step(&mut self) {
         *self.context_mut().current_tick += 1;
-        self.advance_to(self.current_tick().time::<S>());
+        self.advance_to(self.tick_time(self.current_tick()));
     }
 
     fn queue_ticks(&mut self, ticks: u32) {
@@ -343,12 +582,23 @@ impl PrivateSimulationTimeExt for Time<SimulationTime> {
     fn clear_target(&mut self) {
         self.context_mut().target_tick = self.context().current_tick;
     }
+
+    /// Changes the interval between ticks from `tick` onwards.
+    fn set_tick_rate(&mut self, tick: SimulationTick, interval: Duration) {
+        let tick_rate = self.context().tick_rate.changed(tick, interval);
+        self.context_mut().tick_rate = tick_rate;
+    }
 }
 
 pub trait SimulationTimeExt {
     fn current_tick(&self) -> SimulationTick;
 
     fn target_tick(&self) -> SimulationTick;
+
+    fn tick_rate(&self) -> SimulationTickRate;
+
+    /// Calculates the timestamp of a tick with the current [`SimulationTickRate`].
+    fn tick_time(&self, tick: SimulationTick) -> Duration;
 }
 
 impl SimulationTimeExt for Time<SimulationTime> {
@@ -363,4 +613,12 @@ impl SimulationTimeExt for Time<SimulationTime> {
     fn target_tick(&self) -> SimulationTick {
         self.context().target_tick
     }
+
+    fn tick_rate(&self) -> SimulationTickRate {
+        self.context().tick_rate
+    }
+
+    fn tick_time(&self, tick: SimulationTick) -> Duration {
+        self.context().tick_rate.tick_time(tick)
+    }
 }
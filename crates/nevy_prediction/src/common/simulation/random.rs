@@ -0,0 +1,183 @@
+//! Deterministic random number generation for simulation logic.
+//!
+//! Any randomness used during [`SimulationUpdate`](crate::common::simulation::schedules::SimulationUpdate)
+//! must produce the same values on the server and on every client, otherwise the template and prediction worlds will diverge.
+//!
+//! [`SimulationRng`] is part of the simulation state. It holds a seed and produces generators that are keyed by a [`SimulationTick`]
+//! and optionally a [`SimulationEntity`], so every instance of the simulation will produce the same sequence for the same tick and entity.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::common::simulation::{
+    SimulationTick, SimulationTime, SimulationTimeExt,
+    extract_resource::ExtractSimulationResourcePlugin,
+    schedules::{ResetSimulation, SimulationStartup},
+    simulation_entity::SimulationEntity,
+};
+
+pub(crate) fn build(app: &mut App) {
+    app.init_resource::<SimulationRngSeed>();
+    app.init_resource::<SimulationRng>();
+
+    app.add_plugins(ExtractSimulationResourcePlugin::<SimulationRng>::default());
+
+    // Seeded on startup as well as on reset, so instances that are never reset by the server still use the custom seed.
+    app.add_systems(SimulationStartup, reset_simulation_rng);
+    app.add_systems(ResetSimulation, reset_simulation_rng);
+}
+
+/// The seed that [`SimulationRng`] is set to when the simulation is reset.
+///
+/// Insert this resource from the plugin provided by your [`PredictionScheme`](crate::common::scheme::PredictionScheme)
+/// so that it is the same on every instance of the simulation.
+#[derive(Resource, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct SimulationRngSeed(pub u64);
+
+impl Default for SimulationRngSeed {
+    fn default() -> Self {
+        SimulationRngSeed(0x4e45_5659_5052_4544)
+    }
+}
+
+/// Seeded source of randomness that is part of the simulation state.
+///
+/// This resource is extracted between simulation instances and is set to [`SimulationRngSeed`]
+/// during [`SimulationStartup`] and [`ResetSimulation`] on every instance.
+/// If you change the seed at runtime, do it with a world update so that every instance changes it on the same tick.
+///
+/// Generators are derived from the seed and a key instead of from mutable state,
+/// so the order that systems draw numbers in doesn't affect the result.
+/// This also means that requesting a generator for the same tick and key twice will produce the same sequence twice.
+/// Use [`SimulationRng::stream`] with different keys if a system needs multiple independent sequences.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct SimulationRng {
+    seed: u64,
+}
+
+impl Default for SimulationRng {
+    fn default() -> Self {
+        SimulationRng {
+            seed: *SimulationRngSeed::default(),
+        }
+    }
+}
+
+impl SimulationRng {
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Returns a generator for a tick that isn't associated with any entity.
+    pub fn tick(&self, tick: SimulationTick) -> TickRng {
+        self.stream(tick, 0)
+    }
+
+    /// Returns a generator for a particular simulation entity on a tick.
+    pub fn entity(&self, tick: SimulationTick, entity: SimulationEntity) -> TickRng {
+        self.stream(tick, splitmix64(entity.0 ^ 0x656e_7469_7479))
+    }
+
+    /// Returns a generator for an arbitrary key on a tick.
+    pub fn stream(&self, tick: SimulationTick, key: u64) -> TickRng {
+        let mut state = splitmix64(self.seed);
+        state = splitmix64(state ^ *tick as u64);
+        state = splitmix64(state ^ key);
+
+        TickRng { state }
+    }
+}
+
+/// System parameter that returns [`TickRng`]s for the current simulation tick.
+#[derive(SystemParam)]
+pub struct DeterministicRng<'w> {
+    rng: Res<'w, SimulationRng>,
+    time: Res<'w, Time<SimulationTime>>,
+}
+
+impl<'w> DeterministicRng<'w> {
+    /// See [`SimulationRng::tick`].
+    pub fn tick(&self) -> TickRng {
+        self.rng.tick(self.time.current_tick())
+    }
+
+    /// See [`SimulationRng::entity`].
+    pub fn entity(&self, entity: SimulationEntity) -> TickRng {
+        self.rng.entity(self.time.current_tick(), entity)
+    }
+
+    /// See [`SimulationRng::stream`].
+    pub fn stream(&self, key: u64) -> TickRng {
+        self.rng.stream(self.time.current_tick(), key)
+    }
+}
+
+/// A small, fast, deterministic random number generator returned by [`SimulationRng`].
+///
+/// This is a SplitMix64 generator, it is not suitable for anything security related.
+#[derive(Clone, Debug)]
+pub struct TickRng {
+    state: u64,
+}
+
+impl TickRng {
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        splitmix64_finalize(self.state)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a value in the range `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1. / (1u32 << 24) as f32)
+    }
+
+    /// Returns a value in the range `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1. / (1u64 << 53) as f64)
+    }
+
+    /// Returns a value in the range `[start, end)`.
+    ///
+    /// Returns `start` if the range is empty.
+    pub fn range_u32(&mut self, range: std::ops::Range<u32>) -> u32 {
+        let span = range.end.saturating_sub(range.start);
+
+        if span == 0 {
+            return range.start;
+        }
+
+        range.start + ((self.next_u32() as u64 * span as u64) >> 32) as u32
+    }
+
+    /// Returns a value in the range `[start, end)`.
+    pub fn range_f32(&mut self, range: std::ops::Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    /// Returns `true` with the given probability.
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+}
+
+fn splitmix64(value: u64) -> u64 {
+    splitmix64_finalize(value.wrapping_add(0x9e37_79b9_7f4a_7c15))
+}
+
+fn splitmix64_finalize(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Resets the [`SimulationRng`] to the [`SimulationRngSeed`].
+fn reset_simulation_rng(seed: Res<SimulationRngSeed>, mut rng: ResMut<SimulationRng>) {
+    rng.set_seed(**seed);
+}
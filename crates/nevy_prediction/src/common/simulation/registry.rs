@@ -0,0 +1,212 @@
+//! Keeps track of what is registered with an instance of the simulation.
+//!
+//! The [`SimulationRegistry`] records every world update added with
+//! [`AddWorldUpdate::add_world_update`](crate::common::scheme::AddWorldUpdate::add_world_update),
+//! and every component, relation and resource added by the extract and update plugins.
+//! It can be used for diagnostics, tooling, and verifying that the client and server agree on a scheme.
+
+use std::{
+    any::TypeId,
+    hash::{Hash, Hasher},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::common::{
+    scheme::PredictionScheme,
+    simulation::{SimulationTick, UpdateExecutionQueue, WorldUpdate, checksum::ChecksumHasher},
+};
+
+pub(crate) fn build(app: &mut App) {
+    app.init_resource::<SimulationRegistry>();
+}
+
+pub(crate) fn build_update<T>(app: &mut App)
+where
+    T: Send + Sync + 'static + DeserializeOwned,
+{
+    let mut registry = registry_mut(app);
+
+    let index = registry.updates.len();
+    registry.updates.push(WorldUpdateRegistration {
+        index,
+        name: std::any::type_name::<T>(),
+        type_id: TypeId::of::<T>(),
+        queued: queued_updates::<T>,
+        insert_serialized: insert_serialized::<T>,
+    });
+}
+
+/// Records a component added by an [`ExtractSimulationComponentPlugin`](crate::common::simulation::extract_component::ExtractSimulationComponentPlugin).
+pub(crate) fn register_extracted_component<C: 'static>(app: &mut App) {
+    registry_mut(app)
+        .extracted_components
+        .push(TypeRegistration::of::<C>());
+}
+
+/// Records a relation added by an [`ExtractSimulationRelationPlugin`](crate::common::simulation::extract_relation::ExtractSimulationRelationPlugin).
+pub(crate) fn register_extracted_relation<C: 'static>(app: &mut App) {
+    registry_mut(app)
+        .extracted_relations
+        .push(TypeRegistration::of::<C>());
+}
+
+/// Records a resource added by an [`ExtractSimulationResourcePlugin`](crate::common::simulation::extract_resource::ExtractSimulationResourcePlugin).
+pub(crate) fn register_extracted_resource<R: 'static>(app: &mut App) {
+    registry_mut(app)
+        .extracted_resources
+        .push(TypeRegistration::of::<R>());
+}
+
+/// Records a component added by an [`UpdateComponentPlugin`](crate::common::simulation::update_component::UpdateComponentPlugin).
+pub(crate) fn register_updated_component<C: 'static>(app: &mut App) {
+    registry_mut(app)
+        .updated_components
+        .push(TypeRegistration::of::<C>());
+}
+
+fn registry_mut(app: &mut App) -> Mut<'_, SimulationRegistry> {
+    app.init_resource::<SimulationRegistry>();
+    app.world_mut().resource_mut::<SimulationRegistry>()
+}
+
+/// The name and [`TypeId`] of a registered type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TypeRegistration {
+    pub name: &'static str,
+    pub type_id: TypeId,
+}
+
+impl TypeRegistration {
+    fn of<T: 'static>() -> Self {
+        TypeRegistration {
+            name: std::any::type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+        }
+    }
+}
+
+/// Information about a world update type.
+#[derive(Clone, Copy)]
+pub struct WorldUpdateRegistration {
+    /// The position of the world update in the order updates were added.
+    ///
+    /// Because updates must be added in the same order on every instance, this is the same on the client and server.
+    /// It isn't the nevy message id of the update's [`ServerWorldUpdate`](crate::common::ServerWorldUpdate),
+    /// which also depends on the other messages in the protocol.
+    pub index: usize,
+    /// The type name of the world update.
+    pub name: &'static str,
+    pub type_id: TypeId,
+    queued: fn(&World) -> usize,
+    insert_serialized: fn(&mut World, SimulationTick, &[u8]) -> Result,
+}
+
+impl WorldUpdateRegistration {
+    /// Returns how many updates of this type are in its [`UpdateExecutionQueue`].
+    pub fn queued(&self, world: &World) -> usize {
+        (self.queued)(world)
+    }
+
+    /// Deserializes a world update that was serialized with [`bincode`]
+    /// and inserts it into its [`UpdateExecutionQueue`] at the given tick.
+    pub fn insert_serialized(
+        &self,
+        world: &mut World,
+        tick: SimulationTick,
+        data: &[u8],
+    ) -> Result {
+        (self.insert_serialized)(world, tick, data)
+    }
+}
+
+/// Everything registered with this instance of the simulation.
+///
+/// This resource exists in every instance of the simulation.
+#[derive(Resource, Default)]
+pub struct SimulationRegistry {
+    updates: Vec<WorldUpdateRegistration>,
+    extracted_components: Vec<TypeRegistration>,
+    extracted_relations: Vec<TypeRegistration>,
+    extracted_resources: Vec<TypeRegistration>,
+    updated_components: Vec<TypeRegistration>,
+}
+
+impl SimulationRegistry {
+    /// The registered world updates in the order they were added.
+    pub fn updates(&self) -> &[WorldUpdateRegistration] {
+        &self.updates
+    }
+
+    /// Gets the registration of a world update by its type name.
+    pub fn update(&self, name: &str) -> Option<&WorldUpdateRegistration> {
+        self.updates.iter().find(|update| update.name == name)
+    }
+
+    /// Computes the [`ProtocolFingerprint`] of the scheme from the ordered world update names and the step interval.
+    pub fn fingerprint<S>(&self) -> ProtocolFingerprint
+    where
+        S: PredictionScheme,
+    {
+        let mut hasher = ChecksumHasher::default();
+
+        for update in &self.updates {
+            update.name.hash(&mut hasher);
+        }
+
+        S::step_interval().hash(&mut hasher);
+
+        ProtocolFingerprint(hasher.finish())
+    }
+
+    /// Components that are extracted between simulation instances.
+    pub fn extracted_components(&self) -> &[TypeRegistration] {
+        &self.extracted_components
+    }
+
+    /// Relations that are extracted between simulation instances.
+    pub fn extracted_relations(&self) -> &[TypeRegistration] {
+        &self.extracted_relations
+    }
+
+    /// Resources that are extracted between simulation instances.
+    pub fn extracted_resources(&self) -> &[TypeRegistration] {
+        &self.extracted_resources
+    }
+
+    /// Components that can be set with [`UpdateComponent`](crate::common::simulation::update_component::UpdateComponent) world updates.
+    pub fn updated_components(&self) -> &[TypeRegistration] {
+        &self.updated_components
+    }
+}
+
+/// Identifies the world updates of a prediction scheme and the order they were added in.
+///
+/// The client and server exchange fingerprints when a session starts,
+/// because world updates that were added in a different order would be deserialized as the wrong type.
+/// Type names are used, so both sides must be built from the same version of the scheme.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProtocolFingerprint(pub u64);
+
+fn queued_updates<T>(world: &World) -> usize
+where
+    T: Send + Sync + 'static,
+{
+    world
+        .get_resource::<UpdateExecutionQueue<T>>()
+        .map_or(0, |queue| queue.len())
+}
+
+fn insert_serialized<T>(world: &mut World, tick: SimulationTick, data: &[u8]) -> Result
+where
+    T: Send + Sync + 'static + DeserializeOwned,
+{
+    let update: T = bincode::deserialize(data)?;
+
+    world
+        .resource_mut::<UpdateExecutionQueue<T>>()
+        .insert(WorldUpdate { tick, update });
+
+    Ok(())
+}
@@ -0,0 +1,250 @@
+//! Contains the world updates used to resynchronize a client's copy of the simulation with the server.
+//!
+//! Components registered with [`ResyncComponentPlugin`] can be sent to clients by the server when they request a resync,
+//! either for specific [`SimulationEntity`]s or for the whole world.
+//! Relations and resources can be registered with [`ResyncRelationPlugin`] and [`ResyncResourcePlugin`].
+//! The resync is sent as ordinary world updates at the server's current tick,
+//! so the client applies it to its template world without resetting any clocks.
+
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::{component::Mutable, relationship::Relationship},
+    platform::collections::HashSet,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::common::{
+    scheme::AddWorldUpdate,
+    simulation::{
+        ReadyUpdates, SimulationInstance,
+        schedules::SimulationUpdate,
+        simulation_entity::{SimulationEntity, SimulationEntityMap},
+        update_component::UpdateComponentPlugin,
+    },
+};
+
+pub(crate) fn build(app: &mut App) {
+    app.add_world_update::<ResyncSimulationEntities>();
+
+    app.add_systems(
+        SimulationUpdate,
+        apply_resync_simulation_entities.in_set(ResyncSystems),
+    );
+}
+
+/// System set where [`ResyncSimulationEntities`] world updates are applied during [`SimulationUpdate`].
+///
+/// Runs before [`UpdateComponentSystems`](crate::common::simulation::update_component::UpdateComponentSystems)
+/// so that resynced components can be inserted onto entities spawned by the resync.
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResyncSystems;
+
+/// Which part of the simulation a resync applies to.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ResyncTarget {
+    /// Every simulation entity, including spawning and despawning entities so that the set of entities matches the server.
+    World,
+    /// Only the given simulation entities, including spawning and despawning them to match the server.
+    Entities(Vec<SimulationEntity>),
+}
+
+/// A world update that makes the set of simulation entities match the server.
+///
+/// Entities that don't exist locally are spawned, and local entities that aren't in the list are despawned.
+///
+/// This world update is added by default.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ResyncSimulationEntities {
+    pub entities: Vec<SimulationEntity>,
+    /// If set, only these entities are spawned or despawned, and other local entities are left alone.
+    pub scope: Option<Vec<SimulationEntity>>,
+}
+
+fn apply_resync_simulation_entities(
+    mut commands: Commands,
+    mut updates: ReadyUpdates<ResyncSimulationEntities>,
+    map: Res<SimulationEntityMap>,
+    entity_q: Query<(Entity, &SimulationEntity)>,
+) {
+    for ResyncSimulationEntities { entities, scope } in updates.drain() {
+        let entities: HashSet<SimulationEntity> = entities.into_iter().collect();
+        let scope: Option<HashSet<SimulationEntity>> =
+            scope.map(|scope| scope.into_iter().collect());
+
+        for (local_entity, simulation_entity) in &entity_q {
+            let in_scope = scope
+                .as_ref()
+                .is_none_or(|scope| scope.contains(simulation_entity));
+
+            if in_scope && !entities.contains(simulation_entity) {
+                commands.entity(local_entity).try_despawn();
+            }
+        }
+
+        for &simulation_entity in &entities {
+            if map.get(simulation_entity).is_none() {
+                commands.spawn(simulation_entity);
+            }
+        }
+    }
+}
+
+/// A world update that sets a [`Relationship`] component of a simulation entity.
+///
+/// Added by [`ResyncRelationPlugin<C>`].
+#[derive(Serialize, Deserialize)]
+pub struct UpdateRelation<C> {
+    pub entity: SimulationEntity,
+    /// The target of the relation, or `None` if the relation should be removed.
+    pub target: Option<SimulationEntity>,
+    #[serde(skip)]
+    pub _p: PhantomData<C>,
+}
+
+impl<C> Clone for UpdateRelation<C> {
+    fn clone(&self) -> Self {
+        UpdateRelation {
+            entity: self.entity,
+            target: self.target,
+            _p: PhantomData,
+        }
+    }
+}
+
+/// A world update that replaces a resource.
+///
+/// Added by [`ResyncResourcePlugin<R>`].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UpdateResource<R> {
+    pub resource: R,
+}
+
+/// Registers a component to be sent to clients when they request a resync.
+///
+/// This adds an [`UpdateComponentPlugin<C>`], which does nothing if it was already added,
+/// and the server sends the component as [`UpdateComponent<C>`](crate::common::simulation::update_component::UpdateComponent) world updates.
+/// The [`UpdateComponentPlugin<C>`] can still be added before or after this plugin.
+pub struct ResyncComponentPlugin<C>(PhantomData<C>);
+
+impl<C> Default for ResyncComponentPlugin<C> {
+    fn default() -> Self {
+        ResyncComponentPlugin(PhantomData)
+    }
+}
+
+impl<C> Plugin for ResyncComponentPlugin<C>
+where
+    C: Serialize + DeserializeOwned + Clone + Component<Mutability = Mutable>,
+{
+    fn build(&self, app: &mut App) {
+        app.add_plugins(UpdateComponentPlugin::<C>::default());
+
+        if let SimulationInstance::Server = app.world().resource::<SimulationInstance>() {
+            crate::server::resync::build_component::<C>(app);
+        }
+    }
+}
+
+/// Registers a [`Relationship`] component to be sent to clients when they request a resync.
+///
+/// This adds an [`UpdateRelation<C>`] world update.
+pub struct ResyncRelationPlugin<C>(PhantomData<C>);
+
+impl<C> Default for ResyncRelationPlugin<C> {
+    fn default() -> Self {
+        ResyncRelationPlugin(PhantomData)
+    }
+}
+
+impl<C> Plugin for ResyncRelationPlugin<C>
+where
+    C: Component + Relationship,
+{
+    fn build(&self, app: &mut App) {
+        app.add_world_update::<UpdateRelation<C>>();
+
+        app.add_systems(
+            SimulationUpdate,
+            apply_update_relations::<C>
+                .in_set(ResyncSystems)
+                .after(apply_resync_simulation_entities),
+        );
+
+        if let SimulationInstance::Server = app.world().resource::<SimulationInstance>() {
+            crate::server::resync::build_relation::<C>(app);
+        }
+    }
+}
+
+/// Registers a resource to be sent to clients when they request a resync of the whole world.
+///
+/// This adds an [`UpdateResource<R>`] world update.
+pub struct ResyncResourcePlugin<R>(PhantomData<R>);
+
+impl<R> Default for ResyncResourcePlugin<R> {
+    fn default() -> Self {
+        ResyncResourcePlugin(PhantomData)
+    }
+}
+
+impl<R> Plugin for ResyncResourcePlugin<R>
+where
+    R: Resource + Serialize + DeserializeOwned + Clone,
+{
+    fn build(&self, app: &mut App) {
+        app.add_world_update::<UpdateResource<R>>();
+
+        app.add_systems(
+            SimulationUpdate,
+            apply_update_resources::<R>.in_set(ResyncSystems),
+        );
+
+        if let SimulationInstance::Server = app.world().resource::<SimulationInstance>() {
+            crate::server::resync::build_resource::<R>(app);
+        }
+    }
+}
+
+fn apply_update_relations<C>(
+    mut commands: Commands,
+    mut updates: ReadyUpdates<UpdateRelation<C>>,
+    map: Res<SimulationEntityMap>,
+) -> Result
+where
+    C: Component + Relationship,
+{
+    for UpdateRelation { entity, target, .. } in updates.drain() {
+        let local_entity = map.get(entity).ok_or(format!(
+            "Simulation entity {:?} did not exist locally when updating its `{}`",
+            entity,
+            std::any::type_name::<C>()
+        ))?;
+
+        let Some(target) = target else {
+            commands.entity(local_entity).remove::<C>();
+            continue;
+        };
+
+        let local_target = map.get(target).ok_or(format!(
+            "Target simulation entity {:?} did not exist locally when updating the `{}` of {:?}",
+            target,
+            std::any::type_name::<C>(),
+            entity,
+        ))?;
+
+        commands.entity(local_entity).insert(C::from(local_target));
+    }
+
+    Ok(())
+}
+
+fn apply_update_resources<R>(mut commands: Commands, mut updates: ReadyUpdates<UpdateResource<R>>)
+where
+    R: Resource,
+{
+    for UpdateResource { resource } in updates.drain() {
+        commands.insert_resource(resource);
+    }
+}
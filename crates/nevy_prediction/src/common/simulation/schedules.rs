@@ -6,7 +6,7 @@ use bevy::{
     ecs::schedule::{ScheduleLabel, SingleThreadedExecutor},
     prelude::*,
 };
-use tracing::info_span;
+use tracing::{debug, info_span};
 
 /// Runs
 ///
@@ -42,6 +42,12 @@ pub struct SimulationUpdate;
 #[derive(ScheduleLabel, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct SimulationPostUpdate;
 
+/// Runs after [`SimulationPostUpdate`] and hashes the simulation state for desync detection.
+///
+/// See the [`checksum`](crate::common::simulation::checksum) module.
+#[derive(ScheduleLabel, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SimulationChecksum;
+
 /// This schedule resets the simulation instance.
 /// Add systems to this schedule that remove data belonging to the simulation, as well as initialize any new data.
 ///
@@ -66,6 +72,7 @@ pub fn build(app: &mut App) {
     app.add_schedule(Schedule::new(SimulationPreUpdate));
     app.add_schedule(Schedule::new(SimulationUpdate));
     app.add_schedule(Schedule::new(SimulationPostUpdate));
+    app.add_schedule(Schedule::new(SimulationChecksum));
 
     app.add_schedule(Schedule::new(SimulationPreStartup));
     app.add_schedule(Schedule::new(SimulationStartup));
@@ -97,6 +104,10 @@ fn run_simulation_main(world: &mut World) {
     info_span!("SimulationPostUpdate", simulation_instance, simulation_tick).in_scope(|| {
         world.run_schedule(SimulationPostUpdate);
     });
+
+    info_span!("SimulationChecksum", simulation_instance, simulation_tick).in_scope(|| {
+        world.run_schedule(SimulationChecksum);
+    });
 }
 
 fn run_simulation_startup_main(world: &mut World) {
@@ -1,5 +1,5 @@
 use bevy::{
-    ecs::{lifecycle::HookContext, world::DeferredWorld},
+    ecs::{entity_disabling::Disabled, lifecycle::HookContext, query::Allow, world::DeferredWorld},
     platform::collections::HashMap,
     prelude::*,
 };
@@ -9,8 +9,8 @@ use tracing::error;
 use crate::common::{
     scheme::AddWorldUpdate,
     simulation::{
-        ExtractSimulation, ExtractSimulationSystems, ReadyUpdates, SimulationUpdate, SourceWorld,
-        schedules::ResetSimulation,
+        ExtractSimulation, ExtractSimulationSystems, ReadyUpdates, SimulationUpdate,
+        SourceQueryCache, SourceWorld, schedules::ResetSimulation,
     },
 };
 
@@ -132,14 +132,17 @@ fn mark_removed_simulation_entities(
 /// If a simulation entity doesn't exist in the local world, it is spawned.
 ///
 /// If a simulation entity exists in the local world, the [`RemovedSimulationEntity`] component is removed.
+///
+/// [`Disabled`] entities in the source world are included,
+/// see [`PredictionSubset`](crate::client::prediction::PredictionSubset).
 fn extract_simulation_entities(
     mut commands: Commands,
     map: Res<SimulationEntityMap>,
 
-    mut entity_q: Local<Option<QueryState<&SimulationEntity>>>,
+    mut entity_q: Local<SourceQueryCache<&SimulationEntity, Allow<Disabled>>>,
     mut source_world: ResMut<SourceWorld>,
 ) {
-    let entity_q = entity_q.get_or_insert_with(|| source_world.query());
+    let entity_q = source_world.cached_query(&mut entity_q);
 
     for &simulation_entity in entity_q.iter(&*source_world) {
         if let Some(local_entity) = map.get(simulation_entity) {
@@ -9,6 +9,7 @@ use crate::common::{
     simulation::{
         ReadyUpdates,
         extract_resource::ExtractSimulationResourcePlugin,
+        registry,
         schedules::SimulationUpdate,
         simulation_entity::{SimulationEntity, SimulationEntityMap},
     },
@@ -20,6 +21,11 @@ pub struct UpdateComponentSystems;
 
 /// A utility plugin that adds an [`UpdateComponent<C>`] world update,
 /// and the system that applies it during [`UpdateComponentSystems`].
+///
+/// Adding this plugin more than once for the same component does nothing,
+/// so it can be added both directly and through plugins that require it,
+/// such as [`ResyncComponentPlugin<C>`](crate::common::simulation::resync::ResyncComponentPlugin), in any order.
+/// The world update is registered where the plugin is first added.
 pub struct UpdateComponentPlugin<C>(PhantomData<C>);
 
 impl<C> Default for UpdateComponentPlugin<C> {
@@ -33,6 +39,12 @@ where
     C: Serialize + DeserializeOwned + Clone + Component<Mutability = Mutable>,
 {
     fn build(&self, app: &mut App) {
+        if app.world().contains_resource::<UpdateComponentCount<C>>() {
+            return;
+        }
+
+        registry::register_updated_component::<C>(app);
+
         app.add_world_update::<UpdateComponent<C>>();
 
         app.add_systems(
@@ -46,6 +58,10 @@ where
             count: 0,
         });
     }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Resource, Deref, DerefMut, Clone)]
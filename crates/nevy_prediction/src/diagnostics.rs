@@ -0,0 +1,296 @@
+//! Publishes metrics about prediction and networking to bevy's [`DiagnosticsStore`].
+//!
+//! Add the [`PredictionDiagnosticsPlugin`] to a client or server app.
+//! The measurements can be read from the [`DiagnosticsStore`], or logged with bevy's `LogDiagnosticsPlugin`.
+//!
+//! On the client these are published every frame:
+//! - [`RTT`](PredictionDiagnosticsPlugin::RTT), measured by pinging the server.
+//! - [`SERVER_TICK`](PredictionDiagnosticsPlugin::SERVER_TICK), the estimated tick of the server.
+//! - [`TEMPLATE_LAG`](PredictionDiagnosticsPlugin::TEMPLATE_LAG), how many ticks the template world is behind the latest tick from the server.
+//! - [`PREDICTION_DEPTH`](PredictionDiagnosticsPlugin::PREDICTION_DEPTH), how many ticks the main world is ahead of the template world.
+//! - [`RESIMULATED_TICKS`](PredictionDiagnosticsPlugin::RESIMULATED_TICKS), how many ticks the prediction world executed.
+//! - [`EXTRACT_TIME`](PredictionDiagnosticsPlugin::EXTRACT_TIME), the time spent extracting simulation worlds into the main world.
+//!
+//! Per world update type, the server publishes an estimate of the bytes of world updates sent by the
+//! [`WorldUpdateSender`](crate::server::WorldUpdateSender) each frame,
+//! and both the client and server publish the total number of updates that were applied late.
+//! The estimate is the serialized size of the messages, not what was written to the network.
+//! On the client late updates are counted in the template world, because late updates are expected in the prediction world.
+
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticMeasurement, DiagnosticPath, DiagnosticsStore},
+    ecs::{
+        system::{SystemBuffer, SystemMeta},
+        world::DeferredWorld,
+    },
+    platform::{collections::HashMap, time::Instant},
+    prelude::*,
+};
+use tracing::warn;
+
+use crate::{
+    client::{
+        ClientPredictionStream, PredictionServerConnection,
+        template_world::{ServerTickSamples, TemplateWorld},
+    },
+    common::{
+        PredictionPing, PredictionPong,
+        clock::{PredictionClock, SimulationTimeScale},
+        loopback::{PredictionReceiver, PredictionSender},
+        simulation::{LateUpdateCounts, SimulationTime, SimulationTimeExt},
+    },
+};
+
+/// Publishes prediction metrics to the [`DiagnosticsStore`].
+///
+/// See the [`diagnostics`](self) module.
+pub struct PredictionDiagnosticsPlugin {
+    /// How often the client pings the server to measure the round trip time.
+    pub ping_interval: Duration,
+}
+
+impl Default for PredictionDiagnosticsPlugin {
+    fn default() -> Self {
+        PredictionDiagnosticsPlugin {
+            ping_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl PredictionDiagnosticsPlugin {
+    /// Round trip time to the server in milliseconds.
+    pub const RTT: DiagnosticPath = DiagnosticPath::const_new("nevy_prediction/rtt");
+    /// The estimated tick of the server.
+    pub const SERVER_TICK: DiagnosticPath =
+        DiagnosticPath::const_new("nevy_prediction/server_tick");
+    /// Ticks that the template world is behind the latest tick from the server.
+    pub const TEMPLATE_LAG: DiagnosticPath =
+        DiagnosticPath::const_new("nevy_prediction/template_lag");
+    /// Ticks that the main world is ahead of the template world.
+    pub const PREDICTION_DEPTH: DiagnosticPath =
+        DiagnosticPath::const_new("nevy_prediction/prediction_depth");
+    /// Ticks executed by the prediction world in a frame.
+    pub const RESIMULATED_TICKS: DiagnosticPath =
+        DiagnosticPath::const_new("nevy_prediction/resimulated_ticks");
+    /// Milliseconds spent extracting simulation worlds into the main world in a frame.
+    pub const EXTRACT_TIME: DiagnosticPath =
+        DiagnosticPath::const_new("nevy_prediction/extract_time");
+
+    /// The total number of updates of a type that were applied after their tick.
+    pub fn late_updates(type_name: &str) -> DiagnosticPath {
+        DiagnosticPath::new(format!("nevy_prediction/late_updates/{}", type_name))
+    }
+
+    /// Estimated bytes of world updates of a type sent by the server in a frame.
+    ///
+    /// This is the serialized size of the messages, and doesn't include stream or transport overhead.
+    pub fn estimated_bytes_sent(type_name: &str) -> DiagnosticPath {
+        DiagnosticPath::new(format!(
+            "nevy_prediction/estimated_bytes_sent/{}",
+            type_name
+        ))
+    }
+}
+
+impl Plugin for PredictionDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DiagnosticsStore>();
+        app.init_resource::<PredictionFrameStats>();
+        app.init_resource::<UpdateBytesSent>();
+        app.insert_resource(PingInterval(self.ping_interval));
+
+        let mut store = app.world_mut().resource_mut::<DiagnosticsStore>();
+
+        store.add(Diagnostic::new(Self::RTT).with_suffix("ms"));
+        store.add(Diagnostic::new(Self::SERVER_TICK));
+        store.add(Diagnostic::new(Self::TEMPLATE_LAG));
+        store.add(Diagnostic::new(Self::PREDICTION_DEPTH));
+        store.add(Diagnostic::new(Self::RESIMULATED_TICKS));
+        store.add(Diagnostic::new(Self::EXTRACT_TIME).with_suffix("ms"));
+
+        app.add_systems(
+            Last,
+            (
+                (send_pings, receive_pongs, measure_client)
+                    .run_if(resource_exists::<TemplateWorld>),
+                measure_server.run_if(not(resource_exists::<TemplateWorld>)),
+            ),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct PingInterval(Duration);
+
+/// Counters that are measured and reset every frame.
+#[derive(Resource, Default)]
+pub(crate) struct PredictionFrameStats {
+    pub resimulated_ticks: u32,
+    pub extract_time: Duration,
+}
+
+/// Estimated bytes of world updates sent by the server this frame, by type.
+#[derive(Resource, Default)]
+pub(crate) struct UpdateBytesSent(HashMap<&'static str, u64>);
+
+/// Estimated bytes sent are recorded with a [`SystemBuffer`] so that the [`WorldUpdateSender`](crate::server::WorldUpdateSender)
+/// doesn't need mutable access to a resource.
+#[derive(Default, Deref, DerefMut)]
+pub(crate) struct UpdateBytesBuffer(Vec<(&'static str, u64)>);
+
+impl SystemBuffer for UpdateBytesBuffer {
+    fn queue(&mut self, _system_meta: &SystemMeta, mut world: DeferredWorld) {
+        let Some(mut sent) = world.get_resource_mut::<UpdateBytesSent>() else {
+            self.0.clear();
+            return;
+        };
+
+        for (type_name, bytes) in self.0.drain(..) {
+            *sent.0.entry(type_name).or_default() += bytes;
+        }
+    }
+}
+
+/// Adds a measurement, adding the diagnostic first if it doesn't exist.
+fn measure(store: &mut DiagnosticsStore, path: DiagnosticPath, value: f64) {
+    if store.get(&path).is_none() {
+        store.add(Diagnostic::new(path.clone()));
+    }
+
+    if let Some(diagnostic) = store.get_mut(&path) {
+        diagnostic.add_measurement(DiagnosticMeasurement {
+            time: Instant::now(),
+            value,
+        });
+    }
+}
+
+fn measure_late_updates(store: &mut DiagnosticsStore, counts: &LateUpdateCounts) {
+    for (type_name, &count) in counts.iter() {
+        measure(
+            store,
+            PredictionDiagnosticsPlugin::late_updates(type_name),
+            count as f64,
+        );
+    }
+}
+
+fn send_pings(
+    interval: Res<PingInterval>,
+    real_time: Res<Time<Real>>,
+    server_q: Query<Entity, With<PredictionServerConnection>>,
+    mut last_ping: Local<Option<Duration>>,
+    mut messages: PredictionSender<ClientPredictionStream>,
+) -> Result {
+    let now = real_time.elapsed();
+
+    if last_ping.is_some_and(|last_ping| now < last_ping + interval.0) {
+        return Ok(());
+    }
+
+    *last_ping = Some(now);
+
+    for server_entity in &server_q {
+        messages.write(server_entity, true, &PredictionPing { sent: now })?;
+    }
+
+    Ok(())
+}
+
+fn receive_pongs(
+    mut received: PredictionReceiver<PredictionPong, PredictionServerConnection>,
+    real_time: Res<Time<Real>>,
+    mut store: ResMut<DiagnosticsStore>,
+) {
+    for (connection_entity, is_server, PredictionPong { sent }) in received.drain() {
+        if !is_server {
+            warn!(
+                "Received a prediction message from a connection that isn't the server: {}",
+                connection_entity
+            );
+
+            continue;
+        }
+
+        let rtt = real_time.elapsed().saturating_sub(sent);
+
+        measure(
+            &mut store,
+            PredictionDiagnosticsPlugin::RTT,
+            rtt.as_secs_f64() * 1000.,
+        );
+    }
+}
+
+fn measure_client(
+    mut store: ResMut<DiagnosticsStore>,
+    mut stats: ResMut<PredictionFrameStats>,
+    server_time: Res<ServerTickSamples>,
+    template_world: Res<TemplateWorld>,
+    time: Res<Time<SimulationTime>>,
+    time_scale: Res<SimulationTimeScale>,
+    clock: Res<Time<PredictionClock>>,
+) {
+    let template_time = template_world.resource::<Time<SimulationTime>>();
+    let template_tick = template_time.current_tick();
+    let tick_rate = template_time.tick_rate();
+
+    let estimated_time =
+        server_time.estimated_time(tick_rate, time_scale.effective(), clock.elapsed());
+    let server_tick = *tick_rate.since_tick as f64
+        + estimated_time
+            .saturating_sub(tick_rate.since_time)
+            .as_secs_f64()
+            / tick_rate.interval.as_secs_f64();
+
+    measure(
+        &mut store,
+        PredictionDiagnosticsPlugin::SERVER_TICK,
+        server_tick,
+    );
+    measure(
+        &mut store,
+        PredictionDiagnosticsPlugin::TEMPLATE_LAG,
+        server_time.latest().saturating_sub(*template_tick) as f64,
+    );
+    measure(
+        &mut store,
+        PredictionDiagnosticsPlugin::PREDICTION_DEPTH,
+        time.current_tick().saturating_sub(*template_tick) as f64,
+    );
+    measure(
+        &mut store,
+        PredictionDiagnosticsPlugin::RESIMULATED_TICKS,
+        stats.resimulated_ticks as f64,
+    );
+    measure(
+        &mut store,
+        PredictionDiagnosticsPlugin::EXTRACT_TIME,
+        stats.extract_time.as_secs_f64() * 1000.,
+    );
+
+    *stats = default();
+
+    measure_late_updates(&mut store, template_world.resource::<LateUpdateCounts>());
+}
+
+fn measure_server(
+    mut store: ResMut<DiagnosticsStore>,
+    mut bytes_sent: ResMut<UpdateBytesSent>,
+    late_updates: Res<LateUpdateCounts>,
+) {
+    // Types that were sent before are kept so that frames where they weren't sent measure zero bytes.
+    for (&type_name, bytes) in bytes_sent.0.iter_mut() {
+        measure(
+            &mut store,
+            PredictionDiagnosticsPlugin::estimated_bytes_sent(type_name),
+            *bytes as f64,
+        );
+
+        *bytes = 0;
+    }
+
+    measure_late_updates(&mut store, &late_updates);
+}
@@ -1,26 +1,54 @@
 pub mod client;
 pub mod common;
+pub mod diagnostics;
+pub mod offline;
 pub mod server;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub mod prelude {
     pub use crate::client::{
         ClientSimulationSystems, NevyPredictionClientPlugin, PredictionInterval, PredictionRates,
-        PredictionServerConnection, PredictionUpdateCreator, template_world::TemplateWorld,
+        PredictionServerConnection, PredictionUpdateCreator,
+        budget::{AdaptivePredictionBudget, PredictionFallingBehind, SimulationTickCosts},
+        desync::SimulationDesync,
+        prediction::{PredictionExecution, PredictionSubset},
+        recording::{SessionRecorder, SessionRecording, SessionReplay},
+        resync::{AutoResync, RequestResync},
+        session::ResumeSessions,
+        snapshot::SnapshotStatus,
+        spectator::Spectator,
+        template_world::{ServerTickSamples, TemplateWorld},
     };
 
     pub use crate::common::{
-        PredictionMessages, ServerWorldUpdate,
-        scheme::{AddWorldUpdate, PredictionScheme},
+        PredictionMessages, ProtocolMismatch, ServerWorldUpdate, SessionToken,
+        clock::{ManualPredictionTime, PredictionClock, PredictionTimeSource, SimulationTimeScale},
+        loopback::{LoopbackConditions, LoopbackConnection, LoopbackLink, LoopbackSide},
+        scheme::{AddWorldUpdate, PredictionScheme, SynchronizationMode},
         simulation::{
-            ExtractSimulationSystems, ReadyUpdates, SimulationInstance, SimulationTick,
-            SimulationTime, SimulationTimeExt, SourceWorld, StepSimulationSystems,
-            UpdateExecutionQueue, WorldUpdate,
+            ExtractSimulationSystems, LateWorldUpdate, ReadyUpdates, SimulationInstance,
+            SimulationTick, SimulationTickRate, SimulationTime, SimulationTimeExt, SourceWorld,
+            StepSimulationSystems, TickRateUpdate, UpdateExecutionQueue, WorldUpdate,
+            checksum::{
+                ChecksumComponentPlugin, ChecksumInterval, ChecksumResourcePlugin,
+                SimulationChecksums,
+            },
             extract_component::{ExtractComponentSystems, ExtractSimulationComponentPlugin},
             extract_relation::{ExtractRelationSystems, ExtractSimulationRelationPlugin},
             extract_resource::ExtractSimulationResourcePlugin,
+            random::{DeterministicRng, SimulationRng, SimulationRngSeed, TickRng},
+            registry::{
+                ProtocolFingerprint, SimulationRegistry, TypeRegistration, WorldUpdateRegistration,
+            },
+            resync::{
+                ResyncComponentPlugin, ResyncRelationPlugin, ResyncResourcePlugin,
+                ResyncSimulationEntities, ResyncSystems, ResyncTarget, UpdateRelation,
+                UpdateResource,
+            },
             schedules::{
-                ExtractSimulation, SimulationPostUpdate, SimulationPreUpdate, SimulationStartup,
-                SimulationUpdate,
+                ExtractSimulation, SimulationChecksum, SimulationPostUpdate, SimulationPreUpdate,
+                SimulationStartup, SimulationUpdate,
             },
             simulation_entity::{
                 DespawnSimulationEntities, DespawnSimulatonEntity, ExtractDespawnPriority,
@@ -30,7 +58,20 @@ pub mod prelude {
         },
     };
 
+    pub use crate::diagnostics::PredictionDiagnosticsPlugin;
+
+    pub use crate::offline::NevyPredictionOfflinePlugin;
+
     pub use crate::server::{
-        NevyPredictionServerPlugin, PredictionClient, ServerSimulationSystems, WorldUpdateSender,
+        ChangeTickRate, NevyPredictionServerPlugin, PredictionClient, ServerSimulationSystems,
+        WorldUpdateSender,
+        listen::HostUpdateCreator,
+        lockstep::MaxClientUpdateLead,
+        recording::{RecordSystems, ServerRecorder, ServerRecording},
+        replay::{NevyPredictionReplayPlugin, ServerReplay},
+        resync::ResyncRequestCooldown,
+        rooms::{InRoom, RoomClients, SimulationRoom},
+        session::{PredictionSession, ResumedSession, SessionResumeWindow},
+        snapshot::{InitialSnapshot, InitialSnapshotSettings},
     };
 }
@@ -0,0 +1,85 @@
+//! Runs a [`PredictionScheme`] without networking, for single player games.
+//!
+//! Add the [`NevyPredictionOfflinePlugin`] instead of the client or server plugin.
+//! The simulation runs in the main world as a [`SimulationInstance::Offline`],
+//! which is authoritative so there is no template world, prediction world or server to talk to.
+//!
+//! The same game code can be used as on a client.
+//! World updates created with a [`PredictionUpdateCreator`](crate::client::PredictionUpdateCreator)
+//! are queued directly in the simulation, and simulation entities exist in the main world without being extracted.
+
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    prelude::*,
+};
+
+use crate::{
+    common::{
+        clock::PredictionTimeSource,
+        scheme::PredictionScheme,
+        simulation::{SimulationInstance, SimulationPlugin, StepSimulationSystems},
+    },
+    server::drive_simulation_time,
+};
+
+/// Runs the simulation as a [`SimulationInstance::Offline`].
+///
+/// See the [`offline`](self) module.
+pub struct NevyPredictionOfflinePlugin<S> {
+    pub _p: PhantomData<S>,
+    pub schedule: Interned<dyn ScheduleLabel>,
+    /// The clock that drives simulation time.
+    pub time_source: PredictionTimeSource,
+}
+
+impl<S> Default for NevyPredictionOfflinePlugin<S> {
+    fn default() -> Self {
+        NevyPredictionOfflinePlugin {
+            _p: PhantomData,
+            schedule: Update.intern(),
+            time_source: PredictionTimeSource::Real,
+        }
+    }
+}
+
+impl<S> NevyPredictionOfflinePlugin<S> {
+    pub fn new(schedule: impl ScheduleLabel) -> Self {
+        NevyPredictionOfflinePlugin {
+            schedule: schedule.intern(),
+            ..default()
+        }
+    }
+
+    /// Drives simulation time with a different clock.
+    ///
+    /// See the [`clock`](crate::common::clock) module.
+    pub fn with_time_source(mut self, time_source: PredictionTimeSource) -> Self {
+        self.time_source = time_source;
+        self
+    }
+}
+
+impl<S> Plugin for NevyPredictionOfflinePlugin<S>
+where
+    S: PredictionScheme,
+{
+    fn build(&self, app: &mut App) {
+        app.add_plugins(SimulationPlugin::<S> {
+            _p: PhantomData,
+            schedule: self.schedule,
+            instance: SimulationInstance::Offline,
+        });
+
+        crate::common::clock::build(app);
+        app.insert_resource(self.time_source);
+
+        app.add_systems(PreStartup, crate::common::startup_simulation);
+
+        app.add_systems(
+            self.schedule,
+            drive_simulation_time.before(StepSimulationSystems),
+        );
+    }
+}
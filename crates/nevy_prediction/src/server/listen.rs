@@ -0,0 +1,69 @@
+//! Listen server support, where the player hosting the game plays in the same process as the server.
+//!
+//! The server's simulation runs in the main world of the server app,
+//! so a local player doesn't need a connection, a template world or a prediction world.
+//! Rendering and input logic for the local player can query the server's simulation directly,
+//! and there is no latency between creating an update and seeing its effect.
+//!
+//! The local player creates updates with the [`HostUpdateCreator`],
+//! which queues them directly in the server's [`UpdateExecutionQueue`].
+//! Remote clients are [`PredictionClient`]s as normal, and still use client side prediction.
+//!
+//! In [`SynchronizationMode::Lockstep`] the local player's updates are relayed to every remote client,
+//! the same as updates created by a remote client.
+//! In [`SynchronizationMode::State`] informing remote clients is left to the caller, as it is for any other server logic.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        scheme::SynchronizationMode,
+        simulation::{SimulationTimeExt, UpdateExecutionQueue, WorldUpdate},
+    },
+    server::{PredictionClient, WorldUpdateSender},
+};
+
+/// Use this system parameter to create world updates for a player that is hosting a listen server.
+///
+/// See the [`listen`](self) module.
+#[derive(SystemParam)]
+pub struct HostUpdateCreator<'w, 's, T>
+where
+    T: Send + Sync + 'static,
+{
+    sender: WorldUpdateSender<'w, 's>,
+    queue: ResMut<'w, UpdateExecutionQueue<T>>,
+    mode: Res<'w, SynchronizationMode>,
+    client_q: Query<'w, 's, Entity, With<PredictionClient>>,
+}
+
+impl<'w, 's, T> HostUpdateCreator<'w, 's, T>
+where
+    T: Send + Sync + 'static + Serialize + Clone,
+{
+    /// Creates a simulation [`WorldUpdate`] on the current tick and queues it in the server's simulation.
+    ///
+    /// In [`SynchronizationMode::Lockstep`] the update is also sent to every [`PredictionClient`],
+    /// which include it in their prediction.
+    ///
+    /// In [`SynchronizationMode::State`] the update is returned so that it can be sent
+    /// to remote clients with [`WorldUpdateSender::write`] if needed.
+    pub fn create(&mut self, update: T) -> Result<WorldUpdate<T>> {
+        let update = WorldUpdate {
+            tick: self.sender.time.current_tick(),
+            update,
+        };
+
+        self.queue.insert(update.clone());
+
+        if let SynchronizationMode::Lockstep = *self.mode {
+            for client_entity in &self.client_q {
+                self.sender
+                    .write(client_entity, true, true, update.clone())?;
+            }
+        }
+
+        Ok(update)
+    }
+}
@@ -0,0 +1,116 @@
+//! Relays world updates created by clients in [`SynchronizationMode::Lockstep`].
+//!
+//! Only world updates added with [`AddWorldUpdate::add_client_world_update`](crate::common::scheme::AddWorldUpdate::add_client_world_update)
+//! are accepted from clients.
+//! An update is rejected if its tick has already executed,
+//! or if it is more than [`MaxClientUpdateLead`] ticks ahead of the server.
+
+use bevy::prelude::*;
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::warn;
+
+use crate::{
+    common::{
+        ClientWorldUpdate,
+        loopback::PredictionReceiver,
+        scheme::SynchronizationMode,
+        simulation::{SimulationTime, SimulationTimeExt, UpdateExecutionQueue},
+    },
+    server::{
+        PredictionClient, ServerPredictionSchedule, ServerSimulationSystems, WorldUpdateSender,
+    },
+};
+
+pub(crate) fn build_update<T>(app: &mut App)
+where
+    T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone,
+{
+    let SynchronizationMode::Lockstep = app.world().resource::<SynchronizationMode>() else {
+        return;
+    };
+
+    app.init_resource::<MaxClientUpdateLead>();
+
+    let schedule = **app.world().resource::<ServerPredictionSchedule>();
+
+    app.add_systems(
+        schedule,
+        relay_client_world_updates::<T>.in_set(ServerSimulationSystems::QueueUpdates),
+    );
+}
+
+/// How many ticks ahead of the server's current tick a world update from a client can be.
+///
+/// Clients create updates on the tick they are predicting,
+/// so this needs to be larger than their [`PredictionInterval`](crate::client::PredictionInterval) in ticks.
+#[derive(Resource, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct MaxClientUpdateLead(pub u32);
+
+impl Default for MaxClientUpdateLead {
+    fn default() -> Self {
+        MaxClientUpdateLead(32)
+    }
+}
+
+/// Applies world updates sent by clients to the server's simulation and relays them to every client.
+fn relay_client_world_updates<T>(
+    mut received: PredictionReceiver<ClientWorldUpdate<T>, PredictionClient>,
+    client_q: Query<Entity, With<PredictionClient>>,
+    time: Res<Time<SimulationTime>>,
+    max_lead: Res<MaxClientUpdateLead>,
+    mut queue: ResMut<UpdateExecutionQueue<T>>,
+    mut sender: WorldUpdateSender,
+) -> Result
+where
+    T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone,
+{
+    for (requesting_client_entity, is_client, ClientWorldUpdate { update }) in received.drain() {
+        if !is_client {
+            warn!(
+                "Received a world update from a connection that isn't a prediction client: {}",
+                requesting_client_entity
+            );
+
+            continue;
+        }
+
+        // Moving a late update to the current tick would leave the requesting client predicting it on the original tick.
+        if update.tick < time.current_tick() {
+            warn!(
+                "Client {} sent an update `{}` for {:?} which has already executed, rejecting it on {:?}",
+                requesting_client_entity,
+                std::any::type_name::<T>(),
+                update.tick,
+                time.current_tick(),
+            );
+
+            continue;
+        }
+
+        if *update.tick > time.current_tick().saturating_add(**max_lead) {
+            warn!(
+                "Client {} sent an update `{}` for {:?} which is too far ahead of {:?}, rejecting it",
+                requesting_client_entity,
+                std::any::type_name::<T>(),
+                update.tick,
+                time.current_tick(),
+            );
+
+            continue;
+        }
+
+        for client_entity in &client_q {
+            // The requesting client already has this update in its prediction queue.
+            sender.write(
+                client_entity,
+                true,
+                client_entity != requesting_client_entity,
+                update.clone(),
+            )?;
+        }
+
+        queue.insert(update);
+    }
+
+    Ok(())
+}
@@ -1,30 +1,72 @@
 use std::{marker::PhantomData, time::Duration};
 
 use bevy::{
-    ecs::{intern::Interned, schedule::ScheduleLabel, system::SystemParam},
+    ecs::{
+        intern::Interned,
+        schedule::ScheduleLabel,
+        system::{Deferred, SystemParam},
+    },
     prelude::*,
 };
 use nevy::prelude::*;
-use serde::Serialize;
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::{debug, error, warn};
 
-use crate::common::{
-    scheme::PredictionScheme,
-    simulation::{
-        schedules::SimulationPostUpdate, PrivateSimulationTimeExt, SimulationInstance,
-        SimulationPlugin, SimulationTime, SimulationTimeExt, StepSimulationSystems, WorldUpdate,
+use crate::{
+    common::{
+        ClientProtocolFingerprint, PredictionPing, PredictionPong, ProtocolMismatch,
+        ResetClientSimulation, ServerSimulationChecksum, ServerWorldUpdate, UpdateServerTick,
+        UpdateTimeScale,
+        clock::{PredictionClock, PredictionTimeSource, SimulationTimeScale},
+        loopback::{LoopbackSender, PredictionReceiver, PredictionSender},
+        scheme::PredictionScheme,
+        simulation::{
+            PrivateSimulationTimeExt, SimulationInstance, SimulationPlugin, SimulationTick,
+            SimulationTime, SimulationTimeExt, StepSimulationSystems, TickRateUpdate,
+            UpdateExecutionQueue, WorldUpdate,
+            checksum::{ChecksumInterval, ChecksumRegistry, ChecksumSystems, SimulationChecksums},
+            registry::SimulationRegistry,
+            schedules::{SimulationChecksum, SimulationPostUpdate},
+        },
+    },
+    diagnostics::{UpdateBytesBuffer, UpdateBytesSent},
+    server::{
+        rooms::{InRoom, SimulationRoom},
+        session::{PredictionSession, PredictionSessions},
+        snapshot::{InitialSnapshot, InitialSnapshotSettings},
     },
-    ResetClientSimulation, ServerWorldUpdate, UpdateServerTick,
 };
 
+pub mod listen;
+pub mod lockstep;
+pub mod recording;
+pub mod replay;
+pub mod resync;
+pub mod rooms;
+pub mod session;
+pub mod snapshot;
+
 #[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ServerSimulationSystems {
     SendResets,
+    /// Resyncs requested by clients are sent.
+    SendResyncs,
     QueueUpdates,
 }
 
+/// Used to add systems when building server side logic from within the simulation plugin.
+#[derive(Resource, Deref)]
+pub(crate) struct ServerPredictionSchedule(pub Interned<dyn ScheduleLabel>);
+
 pub struct NevyPredictionServerPlugin<S> {
     pub _p: PhantomData<S>,
     pub schedule: Interned<dyn ScheduleLabel>,
+    /// Whether new clients are sent a snapshot of the simulation.
+    ///
+    /// See the [`snapshot`] module.
+    pub initial_snapshot: bool,
+    /// The clock that drives simulation time.
+    pub time_source: PredictionTimeSource,
 }
 
 impl<S> Default for NevyPredictionServerPlugin<S> {
@@ -32,6 +74,8 @@ impl<S> Default for NevyPredictionServerPlugin<S> {
         NevyPredictionServerPlugin {
             _p: PhantomData,
             schedule: Update.intern(),
+            initial_snapshot: false,
+            time_source: PredictionTimeSource::Real,
         }
     }
 }
@@ -43,6 +87,22 @@ impl<S> NevyPredictionServerPlugin<S> {
             ..default()
         }
     }
+
+    /// Sends new clients a snapshot of the simulation.
+    ///
+    /// See the [`snapshot`] module.
+    pub fn with_initial_snapshot(mut self) -> Self {
+        self.initial_snapshot = true;
+        self
+    }
+
+    /// Drives simulation time with a different clock.
+    ///
+    /// See the [`clock`](crate::common::clock) module.
+    pub fn with_time_source(mut self, time_source: PredictionTimeSource) -> Self {
+        self.time_source = time_source;
+        self
+    }
 }
 
 impl<S> Plugin for NevyPredictionServerPlugin<S>
@@ -50,7 +110,11 @@ where
     S: PredictionScheme,
 {
     fn build(&self, app: &mut App) {
+        app.insert_resource(ServerPredictionSchedule(self.schedule));
+
         crate::common::build(app);
+        app.insert_resource(self.time_source);
+        app.add_message::<ChangeTickRate>();
 
         app.add_shared_message_sender::<SimulationUpdatesStream>(
             StreamRequirements::RELIABLE_ORDERED,
@@ -60,30 +124,57 @@ where
             self.schedule,
             (
                 ServerSimulationSystems::SendResets,
+                ServerSimulationSystems::SendResyncs,
                 ServerSimulationSystems::QueueUpdates,
                 StepSimulationSystems,
             )
                 .chain(),
         );
 
+        resync::build(app);
+        session::build(app);
+        snapshot::build(app, self.initial_snapshot);
+
         app.add_plugins(SimulationPlugin::<S> {
             _p: PhantomData,
             schedule: self.schedule,
             instance: SimulationInstance::Server,
         });
 
+        recording::build(app);
+        rooms::build::<S>(app);
+
         app.add_systems(
             self.schedule,
             (
                 send_simulation_resets::<S>.in_set(ServerSimulationSystems::SendResets),
-                drive_simulation_time::<S>.in_set(ServerSimulationSystems::QueueUpdates),
+                verify_client_fingerprints::<S>.in_set(ServerSimulationSystems::SendResets),
+                send_time_scale_updates
+                    .in_set(ServerSimulationSystems::SendResets)
+                    .after(send_simulation_resets::<S>),
+                respond_to_pings.in_set(ServerSimulationSystems::SendResets),
+                send_tick_rate_changes.in_set(ServerSimulationSystems::QueueUpdates),
+                drive_simulation_time.in_set(ServerSimulationSystems::QueueUpdates),
             ),
         );
 
         app.add_systems(SimulationPostUpdate, send_simulation_time_updates::<S>);
+        app.add_systems(
+            SimulationChecksum,
+            send_simulation_checksums.after(ChecksumSystems::Record),
+        );
     }
 }
 
+/// Is called on the server app for each world update message added by the prediction scheme
+pub(crate) fn build_update<T>(app: &mut App)
+where
+    T: Send + Sync + 'static + Serialize + DeserializeOwned + Clone,
+{
+    recording::build_update::<T>(app);
+    session::build_update::<T>(app);
+}
+
 /// Marker type for the simulation updates stream [SharedMessageSender].
 pub struct SimulationUpdatesStream;
 
@@ -91,29 +182,110 @@ pub struct SimulationUpdatesStream;
 #[derive(Component)]
 pub struct PredictionClient;
 
-fn drive_simulation_time<S>(
+pub(crate) fn drive_simulation_time(
     mut time: ResMut<Time<SimulationTime>>,
-    real_time: Res<Time<Real>>,
+    clock: Res<Time<PredictionClock>>,
+    time_scale: Res<SimulationTimeScale>,
     mut overstep: Local<Duration>,
-) where
-    S: PredictionScheme,
-{
-    *overstep += real_time.delta();
+) {
+    *overstep += clock.delta().mul_f32(time_scale.effective());
+
+    // Ticks that are queued but not yet executed will use the current interval,
+    // a change of interval is only applied when the tick that changes it is executed.
+    let interval = time.tick_rate().interval;
 
     loop {
-        if *overstep < S::step_interval() {
+        if *overstep < interval {
             break;
         }
-        *overstep -= S::step_interval();
+        *overstep -= interval;
 
         time.queue_ticks(1);
     }
 }
 
+/// Write this message on the server to change the interval between simulation ticks.
+///
+/// The change is queued as a [`TickRateUpdate`] on the current tick and the new interval takes effect from the next tick.
+/// It is sent to every [`PredictionClient`], so clients switch to the new interval on the same tick.
+/// Every [`SimulationRoom`] changes its tick rate too, see the [`rooms`] module.
+///
+/// Changes to a zero interval are ignored.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct ChangeTickRate {
+    pub interval: Duration,
+}
+
+fn send_tick_rate_changes(
+    mut changes: MessageReader<ChangeTickRate>,
+    client_q: Query<Entity, With<PredictionClient>>,
+    mut queue: ResMut<UpdateExecutionQueue<TickRateUpdate>>,
+    mut sender: WorldUpdateSender,
+) -> Result {
+    for &ChangeTickRate { interval } in changes.read() {
+        if interval.is_zero() {
+            warn!("Ignoring a change of the tick rate to a zero interval");
+
+            continue;
+        }
+
+        let update = WorldUpdate {
+            tick: sender.time.current_tick(),
+            update: TickRateUpdate { interval },
+        };
+
+        debug!(
+            "Changing the tick interval to {:?} on {:?}",
+            interval, update.tick
+        );
+
+        queue.insert(update.clone());
+
+        for client_entity in &client_q {
+            sender.write(client_entity, true, true, update.clone())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the [`SimulationTimeScale`] to new clients, and to every client when it changes.
+///
+/// Clients in a [`SimulationRoom`] are sent it too, because rooms follow the same time scale.
+fn send_time_scale_updates(
+    time_scale: Res<SimulationTimeScale>,
+    client_q: Query<(Entity, Ref<PredictionClient>)>,
+    room_client_q: Query<(Entity, Ref<InRoom>)>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
+) -> Result {
+    let clients = client_q
+        .iter()
+        .map(|(client_entity, client)| (client_entity, client.is_added()));
+    let room_clients = room_client_q
+        .iter()
+        .map(|(client_entity, in_room)| (client_entity, in_room.is_added()));
+
+    for (client_entity, is_new) in clients.chain(room_clients) {
+        if !time_scale.is_changed() && !is_new {
+            continue;
+        }
+
+        messages.write(
+            client_entity,
+            true,
+            &UpdateTimeScale {
+                time_scale: *time_scale,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
 fn send_simulation_time_updates<S>(
     time: Res<Time<SimulationTime>>,
     client_q: Query<Entity, With<PredictionClient>>,
-    mut messages: SharedMessageSender<SimulationUpdatesStream>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
 ) -> Result
 where
     S: PredictionScheme,
@@ -131,20 +303,71 @@ where
     Ok(())
 }
 
+/// Sends the checksums of the tick that was just executed every [`ChecksumInterval`] ticks.
+fn send_simulation_checksums(
+    interval: Res<ChecksumInterval>,
+    registry: Res<ChecksumRegistry>,
+    checksums: Res<SimulationChecksums>,
+    client_q: Query<Entity, With<PredictionClient>>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
+) -> Result {
+    if registry.is_empty() || **interval == 0 {
+        return Ok(());
+    }
+
+    let Some(latest) = checksums.latest() else {
+        return Ok(());
+    };
+
+    if *latest.tick % **interval != 0 {
+        return Ok(());
+    }
+
+    for client_entity in &client_q {
+        messages.write(
+            client_entity,
+            true,
+            &ServerSimulationChecksum(latest.clone()),
+        )?;
+    }
+
+    Ok(())
+}
+
 fn send_simulation_resets<S>(
+    mut commands: Commands,
     new_client_q: Query<Entity, Added<PredictionClient>>,
     time: Res<Time<SimulationTime>>,
-    mut messages: SharedMessageSender<SimulationUpdatesStream>,
+    mut sessions: ResMut<PredictionSessions>,
+    snapshot_settings: Res<InitialSnapshotSettings>,
+    registry: Res<SimulationRegistry>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
 ) -> Result
 where
     S: PredictionScheme,
 {
     for client_entity in &new_client_q {
+        let session = sessions.issue(client_entity);
+
+        commands
+            .entity(client_entity)
+            .insert(PredictionSession(session));
+
+        if snapshot_settings.enabled {
+            commands
+                .entity(client_entity)
+                .insert(InitialSnapshot::default());
+        }
+
         messages.write(
             client_entity,
             true,
             &ResetClientSimulation {
                 simulation_tick: time.current_tick(),
+                session,
+                snapshot: snapshot_settings.enabled,
+                fingerprint: registry.fingerprint::<S>(),
+                tick_rate: time.tick_rate(),
             },
         )?;
     }
@@ -152,6 +375,80 @@ where
     Ok(())
 }
 
+/// Stops sending world updates to clients whose [`ProtocolFingerprint`](crate::common::simulation::registry::ProtocolFingerprint) doesn't match.
+fn verify_client_fingerprints<S>(
+    mut commands: Commands,
+    mut received: PredictionReceiver<ClientProtocolFingerprint, PredictionClient>,
+    room_client_q: Query<(), With<InRoom>>,
+    registry: Res<SimulationRegistry>,
+    mut mismatches: MessageWriter<ProtocolMismatch>,
+) where
+    S: PredictionScheme,
+{
+    let local = registry.fingerprint::<S>();
+
+    for (
+        client_entity,
+        is_client,
+        ClientProtocolFingerprint {
+            fingerprint: remote,
+        },
+    ) in received.drain()
+    {
+        if !is_client && !room_client_q.contains(client_entity) {
+            warn!(
+                "Received a protocol fingerprint from a connection that isn't a prediction client: {}",
+                client_entity
+            );
+
+            continue;
+        }
+
+        if remote == local {
+            continue;
+        }
+
+        error!(
+            "Client {} has a different prediction protocol ({:?}) than the server ({:?}). \
+            World updates must be added in the same order with the same step interval on the client and server. \
+            The client will no longer receive world updates.",
+            client_entity, remote, local
+        );
+
+        mismatches.write(ProtocolMismatch {
+            connection_entity: client_entity,
+            local,
+            remote,
+        });
+
+        commands
+            .entity(client_entity)
+            .remove::<(PredictionClient, InRoom)>();
+    }
+}
+
+/// Responds to pings from clients so that they can measure the round trip time.
+fn respond_to_pings(
+    mut received: PredictionReceiver<PredictionPing, PredictionClient>,
+    room_client_q: Query<(), With<InRoom>>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
+) -> Result {
+    for (client_entity, is_client, PredictionPing { sent }) in received.drain() {
+        if !is_client && !room_client_q.contains(client_entity) {
+            warn!(
+                "Received a ping from a connection that isn't a prediction client: {}",
+                client_entity
+            );
+
+            continue;
+        }
+
+        messages.write(client_entity, true, &PredictionPong { sent })?;
+    }
+
+    Ok(())
+}
+
 /// Use this system parameter to send world updates to clients.
 ///
 /// Which updates are sent to the clients is not controlled by this crate.
@@ -164,13 +461,29 @@ where
 /// You can implement logic that only informs the client about changes that are relevant to it.
 /// This could be the case for a large world, where you only send updates for the client's local area,
 /// or it could be the case for a competitive game where some clients should have information that others don't.
+///
+/// Clients in a [`SimulationRoom`](rooms::SimulationRoom) are sent updates with the tick of their room.
 #[derive(SystemParam)]
 pub struct WorldUpdateSender<'w, 's> {
     pub sender: SharedMessageSender<'w, 's, SimulationUpdatesStream>,
+    loopback: LoopbackSender<'w, 's>,
     pub time: Res<'w, Time<SimulationTime>>,
+    client_room_q: Query<'w, 's, &'static InRoom>,
+    room_q: Query<'w, 's, &'static SimulationRoom>,
+    bytes_sent: Option<Res<'w, UpdateBytesSent>>,
+    bytes_buffer: Deferred<'s, UpdateBytesBuffer>,
 }
 
 impl<'w, 's> WorldUpdateSender<'w, 's> {
+    /// Returns the current tick of the simulation that a client is in,
+    /// which is either the main simulation or the client's room.
+    pub fn current_tick(&self, client_entity: Entity) -> SimulationTick {
+        self.client_room_q
+            .get(client_entity)
+            .and_then(|&InRoom(room_entity)| self.room_q.get(room_entity))
+            .map_or(self.time.current_tick(), SimulationRoom::current_tick)
+    }
+
     /// Sends a [`nevy`] message containing a [`WorldUpdate`] with the current simulation time.
     ///
     /// This method would typically be used when informing clients of [`WorldUpdate`]s generated by the server.
@@ -186,7 +499,7 @@ impl<'w, 's> WorldUpdateSender<'w, 's> {
             queue,
             false,
             WorldUpdate {
-                tick: self.time.current_tick(),
+                tick: self.current_tick(client_entity),
                 update,
             },
         )
@@ -218,14 +531,22 @@ impl<'w, 's> WorldUpdateSender<'w, 's> {
     where
         T: Serialize + Send + Sync + 'static,
     {
-        self.sender.write(
-            client_entity,
-            queue,
-            &ServerWorldUpdate {
-                update,
-                include_in_prediction,
-            },
-        )
+        let message = ServerWorldUpdate {
+            update,
+            include_in_prediction,
+        };
+
+        let sent = self.loopback.write(client_entity, &message)?
+            || self.sender.write(client_entity, queue, &message)?;
+
+        if sent && self.bytes_sent.is_some() {
+            self.bytes_buffer.push((
+                std::any::type_name::<T>(),
+                bincode::serialized_size(&message)?,
+            ));
+        }
+
+        Ok(sent)
     }
 
     /// Gets the underlying [`SharedMessageSender`], for stream operations.
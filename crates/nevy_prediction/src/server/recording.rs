@@ -0,0 +1,344 @@
+//! Records the server's simulation so that it can be replayed bit-for-bit for post-mortem debugging.
+//!
+//! When the [`ServerRecorder`] starts, it captures the simulation entities along with everything registered with the
+//! [`resync`](crate::common::simulation::resync) plugins as world updates at the current tick.
+//! After that, every world update that is applied from an [`UpdateExecutionQueue`] is recorded with the tick it executes on.
+//!
+//! A [`ServerRecording`] can be replayed without networking with the
+//! [`NevyPredictionReplayPlugin`](crate::server::replay::NevyPredictionReplayPlugin).
+//! State that isn't registered for resyncs must be recreated deterministically by the simulation,
+//! so recordings started before the first tick are the most reliable.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use bevy::{ecs::relationship::Relationship, prelude::*};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::common::simulation::{
+    SimulationTick, SimulationTickRate, SimulationTime, SimulationTimeExt, UpdateExecutionQueue,
+    random::SimulationRng,
+    resync::{ResyncSimulationEntities, UpdateRelation, UpdateResource},
+    schedules::SimulationPreUpdate,
+    simulation_entity::SimulationEntity,
+    update_component::UpdateComponent,
+};
+
+pub(crate) fn build(app: &mut App) {
+    app.init_resource::<ServerRecorder>();
+    app.init_resource::<SnapshotCaptures>();
+
+    app.configure_sets(
+        SimulationPreUpdate,
+        (RecordSystems::Begin, RecordSystems::Updates).chain(),
+    );
+
+    app.add_systems(
+        SimulationPreUpdate,
+        begin_recordings.in_set(RecordSystems::Begin),
+    );
+}
+
+pub(crate) fn build_update<T>(app: &mut App)
+where
+    T: Send + Sync + 'static + Serialize,
+{
+    app.add_systems(
+        SimulationPreUpdate,
+        record_world_updates::<T>.in_set(RecordSystems::Updates),
+    );
+}
+
+/// Is called by [`resync::build_component`](crate::server::resync::build_component).
+pub(crate) fn build_component<C>(app: &mut App)
+where
+    C: Serialize + Clone + Component,
+{
+    app.init_resource::<SnapshotCaptures>();
+    app.world_mut()
+        .resource_mut::<SnapshotCaptures>()
+        .push(capture_components::<C>);
+}
+
+/// Is called by [`resync::build_relation`](crate::server::resync::build_relation).
+pub(crate) fn build_relation<C>(app: &mut App)
+where
+    C: Component + Relationship,
+{
+    app.init_resource::<SnapshotCaptures>();
+    app.world_mut()
+        .resource_mut::<SnapshotCaptures>()
+        .push(capture_relations::<C>);
+}
+
+/// Is called by [`resync::build_resource`](crate::server::resync::build_resource).
+pub(crate) fn build_resource<R>(app: &mut App)
+where
+    R: Resource + Serialize + Clone,
+{
+    app.init_resource::<SnapshotCaptures>();
+    app.world_mut()
+        .resource_mut::<SnapshotCaptures>()
+        .push(capture_resource::<R>);
+}
+
+/// System sets in [`SimulationPreUpdate`] where the server's simulation is recorded.
+///
+/// World updates inserted into an [`UpdateExecutionQueue`] after [`RecordSystems::Updates`] for the current tick
+/// are applied late and won't be recorded.
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RecordSystems {
+    /// A recording that was started captures the initial state.
+    Begin,
+    /// World updates that are ready to be applied are recorded.
+    Updates,
+}
+
+/// A serialized world update, as stored in a [`ServerRecording`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedWorldUpdate {
+    /// The type name of the world update.
+    pub update_type: String,
+    pub tick: SimulationTick,
+    /// The world update serialized with [`bincode`].
+    pub data: Vec<u8>,
+}
+
+impl RecordedWorldUpdate {
+    fn new<T>(tick: SimulationTick, update: &T) -> Result<Self>
+    where
+        T: Serialize,
+    {
+        Ok(RecordedWorldUpdate {
+            update_type: std::any::type_name::<T>().into(),
+            tick,
+            data: bincode::serialize(update)?,
+        })
+    }
+}
+
+/// A recording of the server's simulation.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ServerRecording {
+    /// The tick the recording started on.
+    pub start_tick: SimulationTick,
+    /// The last tick that was recorded.
+    pub end_tick: SimulationTick,
+    /// The seed of the [`SimulationRng`].
+    pub rng_seed: u64,
+    /// The tick rate of the simulation at the start tick.
+    ///
+    /// Changes to the tick rate during the recording are recorded as [`TickRateUpdate`](crate::common::simulation::TickRateUpdate)s.
+    pub tick_rate: Option<SimulationTickRate>,
+    /// The world updates that recreate the state of the simulation at the start tick.
+    pub snapshot: Vec<RecordedWorldUpdate>,
+    /// Every world update that was applied, in the order they were applied.
+    pub updates: Vec<RecordedWorldUpdate>,
+}
+
+impl ServerRecording {
+    /// Writes the recording to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result {
+        let file = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(file, self)?;
+
+        Ok(())
+    }
+
+    /// Reads a recording from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+
+        Ok(bincode::deserialize_from(file)?)
+    }
+}
+
+/// Records the server's simulation.
+///
+/// Nothing is recorded until [`ServerRecorder::start`] is called.
+#[derive(Resource, Default)]
+pub struct ServerRecorder {
+    recording: Option<ServerRecording>,
+    started: bool,
+}
+
+impl ServerRecorder {
+    /// Starts a new recording on the next tick, discarding any recording in progress.
+    pub fn start(&mut self) {
+        self.recording = Some(ServerRecording::default());
+        self.started = false;
+    }
+
+    /// Stops recording, returning the recording if one was in progress.
+    pub fn stop(&mut self) -> Option<ServerRecording> {
+        self.started = false;
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+}
+
+/// Captures the state of one registered type as world updates on a tick.
+type CaptureSnapshot = fn(&mut World, SimulationTick, &mut Vec<RecordedWorldUpdate>) -> Result;
+
+/// Functions that capture the state registered for resyncs as world updates.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct SnapshotCaptures(Vec<CaptureSnapshot>);
+
+fn begin_recordings(world: &mut World) -> Result {
+    let time = world.resource::<Time<SimulationTime>>();
+    let tick = time.current_tick();
+    let tick_rate = time.tick_rate();
+
+    let mut recorder = world.resource_mut::<ServerRecorder>();
+
+    if recorder.started {
+        if let Some(recording) = &mut recorder.recording {
+            recording.end_tick = tick;
+        }
+
+        return Ok(());
+    }
+
+    let Some(mut recording) = recorder.recording.take() else {
+        return Ok(());
+    };
+
+    debug!("started recording the simulation on {:?}", tick);
+
+    recording.start_tick = tick;
+    recording.end_tick = tick;
+    recording.rng_seed = world.resource::<SimulationRng>().seed();
+    recording.tick_rate = Some(tick_rate);
+
+    let entities: Vec<SimulationEntity> = world
+        .query::<&SimulationEntity>()
+        .iter(world)
+        .copied()
+        .collect();
+
+    recording.snapshot.push(RecordedWorldUpdate::new(
+        tick,
+        &ResyncSimulationEntities {
+            entities,
+            scope: None,
+        },
+    )?);
+
+    let captures = world.resource::<SnapshotCaptures>().0.clone();
+
+    for capture in captures {
+        capture(world, tick, &mut recording.snapshot)?;
+    }
+
+    let mut recorder = world.resource_mut::<ServerRecorder>();
+    recorder.recording = Some(recording);
+    recorder.started = true;
+
+    Ok(())
+}
+
+fn record_world_updates<T>(
+    mut recorder: ResMut<ServerRecorder>,
+    queue: Res<UpdateExecutionQueue<T>>,
+    time: Res<Time<SimulationTime>>,
+) -> Result
+where
+    T: Send + Sync + 'static + Serialize,
+{
+    let Some(recording) = &mut recorder.recording else {
+        return Ok(());
+    };
+
+    let tick = time.current_tick();
+
+    // Late updates are recorded on the tick they are actually applied so that the replay matches.
+    for update in queue.iter().take_while(|update| update.tick <= tick) {
+        recording
+            .updates
+            .push(RecordedWorldUpdate::new(tick, &update.update)?);
+    }
+
+    Ok(())
+}
+
+fn capture_components<C>(
+    world: &mut World,
+    tick: SimulationTick,
+    out: &mut Vec<RecordedWorldUpdate>,
+) -> Result
+where
+    C: Serialize + Clone + Component,
+{
+    for (&entity, component) in world.query::<(&SimulationEntity, &C)>().iter(world) {
+        out.push(RecordedWorldUpdate::new(
+            tick,
+            &UpdateComponent {
+                entity,
+                component: component.clone(),
+            },
+        )?);
+    }
+
+    Ok(())
+}
+
+fn capture_relations<C>(
+    world: &mut World,
+    tick: SimulationTick,
+    out: &mut Vec<RecordedWorldUpdate>,
+) -> Result
+where
+    C: Component + Relationship,
+{
+    let relations: Vec<(SimulationEntity, Entity)> = world
+        .query::<(&SimulationEntity, &C)>()
+        .iter(world)
+        .map(|(&entity, relation)| (entity, relation.get()))
+        .collect();
+
+    for (entity, target) in relations {
+        let Some(&target) = world.get::<SimulationEntity>(target) else {
+            continue;
+        };
+
+        out.push(RecordedWorldUpdate::new(
+            tick,
+            &UpdateRelation::<C> {
+                entity,
+                target: Some(target),
+                _p: default(),
+            },
+        )?);
+    }
+
+    Ok(())
+}
+
+fn capture_resource<R>(
+    world: &mut World,
+    tick: SimulationTick,
+    out: &mut Vec<RecordedWorldUpdate>,
+) -> Result
+where
+    R: Resource + Serialize + Clone,
+{
+    let Some(resource) = world.get_resource::<R>() else {
+        return Ok(());
+    };
+
+    out.push(RecordedWorldUpdate::new(
+        tick,
+        &UpdateResource {
+            resource: resource.clone(),
+        },
+    )?);
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Replays a [`ServerRecording`] without networking.
+//!
+//! Add the [`NevyPredictionReplayPlugin`] instead of the [`NevyPredictionServerPlugin`](crate::server::NevyPredictionServerPlugin)
+//! and insert a [`ServerReplay`] resource.
+//! The simulation is reset to the start of the recording and every recorded world update is queued on the tick it was applied on.
+//! The simulation then advances at the same rate as the server until the end of the recording.
+
+use std::{marker::PhantomData, time::Duration};
+
+use bevy::{
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    prelude::*,
+};
+use tracing::debug;
+
+use crate::{
+    common::{
+        clock::{PredictionClock, SimulationTimeScale},
+        scheme::PredictionScheme,
+        simulation::{
+            PrivateSimulationTimeExt, SimulationInstance, SimulationPlugin, SimulationTickRate,
+            SimulationTime, SimulationTimeExt, StepSimulationSystems, random::SimulationRngSeed,
+            registry::SimulationRegistry, schedules::ResetSimulation,
+        },
+    },
+    server::recording::{RecordedWorldUpdate, ServerRecording},
+};
+
+/// Runs the simulation as a [`SimulationInstance::Replay`] to replay a [`ServerRecording`].
+pub struct NevyPredictionReplayPlugin<S> {
+    pub _p: PhantomData<S>,
+    pub schedule: Interned<dyn ScheduleLabel>,
+}
+
+impl<S> Default for NevyPredictionReplayPlugin<S> {
+    fn default() -> Self {
+        NevyPredictionReplayPlugin {
+            _p: PhantomData,
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl<S> NevyPredictionReplayPlugin<S> {
+    pub fn new(schedule: impl ScheduleLabel) -> Self {
+        NevyPredictionReplayPlugin {
+            schedule: schedule.intern(),
+            ..default()
+        }
+    }
+}
+
+impl<S> Plugin for NevyPredictionReplayPlugin<S>
+where
+    S: PredictionScheme,
+{
+    fn build(&self, app: &mut App) {
+        app.add_plugins(SimulationPlugin::<S> {
+            _p: PhantomData,
+            schedule: self.schedule,
+            instance: SimulationInstance::Replay,
+        });
+
+        crate::common::clock::build(app);
+
+        app.add_systems(PreStartup, crate::common::startup_simulation);
+
+        app.add_systems(
+            self.schedule,
+            (start_replay::<S>, drive_replay_time)
+                .chain()
+                .before(StepSimulationSystems),
+        );
+    }
+}
+
+/// Insert this resource to replay a [`ServerRecording`].
+#[derive(Resource)]
+pub struct ServerReplay {
+    recording: ServerRecording,
+    started: bool,
+    overstep: Duration,
+}
+
+impl ServerReplay {
+    pub fn new(recording: ServerRecording) -> Self {
+        ServerReplay {
+            recording,
+            started: false,
+            overstep: Duration::ZERO,
+        }
+    }
+
+    pub fn recording(&self) -> &ServerRecording {
+        &self.recording
+    }
+
+    /// Returns `true` once the last recorded tick has been executed.
+    pub fn is_finished(&self, time: &Time<SimulationTime>) -> bool {
+        self.started && time.current_tick() > self.recording.end_tick
+    }
+}
+
+fn start_replay<S>(world: &mut World) -> Result
+where
+    S: PredictionScheme,
+{
+    let Some(mut replay) = world.get_resource_mut::<ServerReplay>() else {
+        return Ok(());
+    };
+
+    if replay.started {
+        return Ok(());
+    }
+
+    replay.started = true;
+
+    let start_tick = replay.recording.start_tick;
+    let rng_seed = replay.recording.rng_seed;
+    let tick_rate = replay
+        .recording
+        .tick_rate
+        .unwrap_or_else(SimulationTickRate::new::<S>);
+
+    debug!("replaying the simulation from {:?}", start_tick);
+
+    world.insert_resource(Time::<SimulationTime>::from_tick(start_tick, tick_rate));
+    world.insert_resource(SimulationRngSeed(rng_seed));
+    world.run_schedule(ResetSimulation);
+
+    world.resource_scope(|world, replay: Mut<ServerReplay>| {
+        let recording = &replay.recording;
+
+        for update in recording.snapshot.iter().chain(recording.updates.iter()) {
+            insert_recorded_update(world, update)?;
+        }
+
+        Ok(())
+    })
+}
+
+fn insert_recorded_update(world: &mut World, update: &RecordedWorldUpdate) -> Result {
+    let registration = *world
+        .resource::<SimulationRegistry>()
+        .update(&update.update_type)
+        .ok_or(format!(
+            "The recording contains a world update `{}` that hasn't been added",
+            update.update_type
+        ))?;
+
+    registration.insert_serialized(world, update.tick, &update.data)
+}
+
+fn drive_replay_time(
+    replay: Option<ResMut<ServerReplay>>,
+    mut time: ResMut<Time<SimulationTime>>,
+    clock: Res<Time<PredictionClock>>,
+    time_scale: Res<SimulationTimeScale>,
+) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+
+    replay.overstep += clock.delta().mul_f32(time_scale.effective());
+
+    let interval = time.tick_rate().interval;
+
+    while replay.overstep >= interval {
+        replay.overstep -= interval;
+
+        if time.target_tick() > replay.recording.end_tick {
+            break;
+        }
+
+        time.queue_ticks(1);
+    }
+}
@@ -0,0 +1,415 @@
+//! Responds to [`ResyncRequest`]s from clients with authoritative copies of registered components.
+//!
+//! A client's requests are dropped until [`ResyncRequestCooldown`] ticks have passed since its last accepted request.
+//!
+//! See the [`resync`](crate::common::simulation::resync) module.
+
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::{component::Mutable, relationship::Relationship},
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{
+    common::{
+        ResyncRequest,
+        loopback::PredictionReceiver,
+        simulation::{
+            SimulationTick, SimulationTime, SimulationTimeExt,
+            resync::{ResyncSimulationEntities, ResyncTarget, UpdateRelation, UpdateResource},
+            schedules::{ResetSimulation, SimulationPostUpdate},
+            simulation_entity::SimulationEntity,
+            update_component::UpdateComponent,
+        },
+    },
+    server::{
+        PredictionClient, ServerPredictionSchedule, ServerSimulationSystems, WorldUpdateSender,
+        session::{UnresyncableChanges, register_resynced_update},
+    },
+};
+
+pub(crate) fn build(app: &mut App) {
+    let schedule = **app.world().resource::<ServerPredictionSchedule>();
+
+    app.init_resource::<PendingResyncs>();
+    app.init_resource::<ResyncRequestCooldown>();
+
+    app.configure_sets(
+        schedule,
+        (
+            ServerResyncSystems::ReceiveRequests,
+            ServerResyncSystems::SendEntities,
+            ServerResyncSystems::SendComponents,
+            ServerResyncSystems::Clear,
+        )
+            .chain()
+            .in_set(ServerSimulationSystems::SendResyncs),
+    );
+
+    app.add_systems(
+        schedule,
+        (
+            receive_resync_requests.in_set(ServerResyncSystems::ReceiveRequests),
+            send_entity_resyncs.in_set(ServerResyncSystems::SendEntities),
+            clear_pending_resyncs.in_set(ServerResyncSystems::Clear),
+        ),
+    );
+}
+
+/// Is called on the server app for each component added by a
+/// [`ResyncComponentPlugin`](crate::common::simulation::resync::ResyncComponentPlugin).
+pub(crate) fn build_component<C>(app: &mut App)
+where
+    C: Serialize + Clone + Component<Mutability = Mutable>,
+{
+    let schedule = **app.world().resource::<ServerPredictionSchedule>();
+
+    app.init_resource::<ComponentChangeTicks<C>>();
+    register_resynced_update::<UpdateComponent<C>>(app);
+
+    app.add_systems(
+        schedule,
+        send_component_resyncs::<C>.in_set(ServerResyncSystems::SendComponents),
+    );
+
+    app.add_systems(SimulationPostUpdate, track_component_changes::<C>);
+    app.add_systems(ResetSimulation, reset_component_change_ticks::<C>);
+
+    crate::server::recording::build_component::<C>(app);
+}
+
+/// Is called on the server app for each relation added by a
+/// [`ResyncRelationPlugin`](crate::common::simulation::resync::ResyncRelationPlugin).
+pub(crate) fn build_relation<C>(app: &mut App)
+where
+    C: Component + Relationship,
+{
+    let schedule = **app.world().resource::<ServerPredictionSchedule>();
+
+    register_resynced_update::<UpdateRelation<C>>(app);
+
+    app.add_systems(
+        schedule,
+        send_relation_resyncs::<C>.in_set(ServerResyncSystems::SendComponents),
+    );
+
+    crate::server::recording::build_relation::<C>(app);
+}
+
+/// Is called on the server app for each resource added by a
+/// [`ResyncResourcePlugin`](crate::common::simulation::resync::ResyncResourcePlugin).
+pub(crate) fn build_resource<R>(app: &mut App)
+where
+    R: Resource + Serialize + Clone,
+{
+    let schedule = **app.world().resource::<ServerPredictionSchedule>();
+
+    register_resynced_update::<UpdateResource<R>>(app);
+
+    app.add_systems(
+        schedule,
+        send_resource_resyncs::<R>.in_set(ServerResyncSystems::SendComponents),
+    );
+
+    crate::server::recording::build_resource::<R>(app);
+}
+
+/// How many ticks a client has to wait after a resync request before another one is accepted.
+///
+/// Resyncs can be large, so this stops a client from making the server send them every frame.
+#[derive(Resource, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct ResyncRequestCooldown(pub u32);
+
+impl Default for ResyncRequestCooldown {
+    fn default() -> Self {
+        ResyncRequestCooldown(10)
+    }
+}
+
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum ServerResyncSystems {
+    ReceiveRequests,
+    SendEntities,
+    SendComponents,
+    Clear,
+}
+
+/// A resync that will be sent to a client this frame.
+pub(crate) struct PendingResync {
+    pub client_entity: Entity,
+    /// `None` if the whole world should be resynced.
+    pub entities: Option<HashSet<SimulationEntity>>,
+    /// If set, only components that changed on or after this tick are sent.
+    pub since: Option<SimulationTick>,
+    /// Whether resources registered for resyncs should be sent.
+    pub resources: bool,
+}
+
+impl PendingResync {
+    fn includes(&self, entity: SimulationEntity) -> bool {
+        match &self.entities {
+            None => true,
+            Some(entities) => entities.contains(&entity),
+        }
+    }
+}
+
+/// The tick that a resynced component last changed on for each simulation entity.
+#[derive(Resource)]
+pub(crate) struct ComponentChangeTicks<C> {
+    _p: PhantomData<C>,
+    ticks: HashMap<SimulationEntity, SimulationTick>,
+}
+
+impl<C> Default for ComponentChangeTicks<C> {
+    fn default() -> Self {
+        ComponentChangeTicks {
+            _p: PhantomData,
+            ticks: HashMap::default(),
+        }
+    }
+}
+
+impl<C> ComponentChangeTicks<C> {
+    fn changed_since(&self, entity: SimulationEntity, since: SimulationTick) -> bool {
+        // Entities without a recorded change are sent to be safe.
+        self.ticks.get(&entity).is_none_or(|&tick| tick >= since)
+    }
+}
+
+/// Resyncs that will be sent to clients during [`ServerSimulationSystems::SendResyncs`].
+#[derive(Resource, Default, Deref, DerefMut)]
+pub(crate) struct PendingResyncs(Vec<PendingResync>);
+
+fn receive_resync_requests(
+    mut received: PredictionReceiver<ResyncRequest, PredictionClient>,
+    mut pending: ResMut<PendingResyncs>,
+    cooldown: Res<ResyncRequestCooldown>,
+    time: Res<Time<SimulationTime>>,
+    client_q: Query<(), With<PredictionClient>>,
+    mut last_requests: Local<HashMap<Entity, SimulationTick>>,
+) {
+    last_requests.retain(|&client_entity, _| client_q.contains(client_entity));
+
+    let current_tick = time.current_tick();
+
+    for (client_entity, is_client, ResyncRequest { target }) in received.drain() {
+        if !is_client {
+            warn!(
+                "Received a resync request from a connection that isn't a prediction client: {}",
+                client_entity
+            );
+
+            continue;
+        }
+
+        if let Some(&last_tick) = last_requests.get(&client_entity)
+            && *current_tick < last_tick.saturating_add(**cooldown)
+        {
+            warn!(
+                "Dropping a resync request from {} because it is within the cooldown of its last request",
+                client_entity
+            );
+
+            continue;
+        }
+
+        last_requests.insert(client_entity, current_tick);
+
+        let (entities, resources) = match target {
+            ResyncTarget::World => (None, true),
+            ResyncTarget::Entities(entities) => (Some(entities.into_iter().collect()), false),
+        };
+
+        pending.push(PendingResync {
+            client_entity,
+            entities,
+            since: None,
+            resources,
+        });
+    }
+}
+
+/// Sends which simulation entities exist, so that clients spawn the ones they are missing and despawn the ones that were removed.
+///
+/// Targeted resyncs only spawn or despawn the requested entities.
+fn send_entity_resyncs(
+    pending: Res<PendingResyncs>,
+    entity_q: Query<&SimulationEntity>,
+    mut sender: WorldUpdateSender,
+) -> Result {
+    for resync in pending.iter() {
+        let update = match &resync.entities {
+            None => ResyncSimulationEntities {
+                entities: entity_q.iter().copied().collect(),
+                scope: None,
+            },
+            Some(requested) => ResyncSimulationEntities {
+                entities: entity_q
+                    .iter()
+                    .copied()
+                    .filter(|entity| requested.contains(entity))
+                    .collect(),
+                scope: Some(requested.iter().copied().collect()),
+            },
+        };
+
+        sender.write_now(resync.client_entity, true, update)?;
+    }
+
+    Ok(())
+}
+
+fn send_component_resyncs<C>(
+    pending: Res<PendingResyncs>,
+    change_ticks: Res<ComponentChangeTicks<C>>,
+    component_q: Query<(&SimulationEntity, &C)>,
+    mut sender: WorldUpdateSender,
+) -> Result
+where
+    C: Serialize + Clone + Component<Mutability = Mutable>,
+{
+    for resync in pending.iter() {
+        for (&entity, component) in &component_q {
+            if !resync.includes(entity) {
+                continue;
+            }
+
+            if resync
+                .since
+                .is_some_and(|since| !change_ticks.changed_since(entity, since))
+            {
+                continue;
+            }
+
+            sender.write_now(
+                resync.client_entity,
+                true,
+                UpdateComponent {
+                    entity,
+                    component: component.clone(),
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn send_relation_resyncs<C>(
+    pending: Res<PendingResyncs>,
+    entity_q: Query<(&SimulationEntity, Option<&C>)>,
+    simulation_entity_q: Query<&SimulationEntity>,
+    mut sender: WorldUpdateSender,
+) -> Result
+where
+    C: Component + Relationship,
+{
+    for resync in pending.iter() {
+        for (&entity, relation) in &entity_q {
+            if !resync.includes(entity) {
+                continue;
+            }
+
+            let target = match relation {
+                None => None,
+                Some(relation) => {
+                    let Ok(&target) = simulation_entity_q.get(relation.get()) else {
+                        warn!(
+                            "The `{}` of {:?} targets an entity that isn't a simulation entity",
+                            std::any::type_name::<C>(),
+                            entity,
+                        );
+
+                        continue;
+                    };
+
+                    Some(target)
+                }
+            };
+
+            sender.write_now(
+                resync.client_entity,
+                true,
+                UpdateRelation {
+                    entity,
+                    target,
+                    _p: PhantomData::<C>,
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn send_resource_resyncs<R>(
+    pending: Res<PendingResyncs>,
+    resource: Option<Res<R>>,
+    mut sender: WorldUpdateSender,
+) -> Result
+where
+    R: Resource + Serialize + Clone,
+{
+    let Some(resource) = resource else {
+        return Ok(());
+    };
+
+    for resync in pending.iter() {
+        if !resync.resources {
+            continue;
+        }
+
+        sender.write_now(
+            resync.client_entity,
+            true,
+            UpdateResource {
+                resource: resource.clone(),
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+fn clear_pending_resyncs(mut pending: ResMut<PendingResyncs>) {
+    pending.clear();
+}
+
+/// Records the tick that components changed on so that resumed sessions only receive what changed.
+///
+/// Removals can't be resent, so they prevent sessions from being resumed.
+fn track_component_changes<C>(
+    mut change_ticks: ResMut<ComponentChangeTicks<C>>,
+    mut unresyncable: ResMut<UnresyncableChanges>,
+    mut removed: RemovedComponents<C>,
+    component_q: Query<(&SimulationEntity, Ref<C>)>,
+    entity_q: Query<(), With<SimulationEntity>>,
+    time: Res<Time<SimulationTime>>,
+) where
+    C: Component,
+{
+    for (&entity, component) in &component_q {
+        if component.is_changed() {
+            change_ticks.ticks.insert(entity, time.current_tick());
+        }
+    }
+
+    // Despawned entities are resent with the set of simulation entities.
+    for entity in removed.read() {
+        if entity_q.contains(entity) {
+            unresyncable.record(time.current_tick());
+        }
+    }
+}
+
+fn reset_component_change_ticks<C>(mut change_ticks: ResMut<ComponentChangeTicks<C>>)
+where
+    C: Component,
+{
+    change_ticks.ticks.clear();
+}
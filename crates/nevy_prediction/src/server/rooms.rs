@@ -0,0 +1,265 @@
+//! Rooms host additional simulations on the server, each in its own [`World`] with its own tick.
+//!
+//! Spawn an entity with a [`SimulationRoom`] and insert [`InRoom`] onto a client's connection entity to assign it to the room.
+//! Room clients should not have a [`PredictionClient`] component, which is for clients of the server's main simulation.
+//!
+//! When a client is assigned to a room it is reset to the room's simulation,
+//! and from then on it receives the room's ticks.
+//! World updates sent with [`WorldUpdateSender::write_now`](crate::server::WorldUpdateSender::write_now)
+//! are timestamped with the tick of the client's room,
+//! and should be queued in the room with [`SimulationRoom::queue_update`].
+//!
+//! Rooms advance with the [`PredictionClock`] and the server's [`SimulationTimeScale`], like the main simulation.
+//! A [`ChangeTickRate`] changes the tick rate of every room as well as the main simulation.
+//! Room clients are sent the time scale and tick rate changes, so they follow their room.
+//!
+//! Rooms don't support initial snapshots, resyncs, session resumes, lockstep or checksums.
+//! Clients in rooms should have [`ResumeSessions`](crate::client::session::ResumeSessions) disabled.
+
+use std::{marker::PhantomData, time::Duration};
+
+use bevy::{ecs::schedule::ScheduleLabel, prelude::*};
+use tracing::{debug, error, warn};
+
+use crate::{
+    client::simulation_world::SimulationWorld,
+    common::{
+        ResetClientSimulation, ServerWorldUpdate, UpdateServerTick,
+        clock::{PredictionClock, SimulationTimeScale},
+        loopback::PredictionSender,
+        scheme::PredictionScheme,
+        simulation::{
+            LateWorldUpdate, SimulationInstance, SimulationPlugin, SimulationTick,
+            SimulationTickRate, SimulationTime, SimulationTimeExt, StepSimulationSystems,
+            TickRateUpdate, UpdateExecutionQueue, WorldUpdate, registry::SimulationRegistry,
+        },
+    },
+    server::{
+        ChangeTickRate, PredictionClient, ServerPredictionSchedule, ServerSimulationSystems,
+        SimulationUpdatesStream,
+        session::{PredictionSession, PredictionSessions},
+    },
+};
+
+pub(crate) fn build<S>(app: &mut App)
+where
+    S: PredictionScheme,
+{
+    let schedule = **app.world().resource::<ServerPredictionSchedule>();
+
+    app.add_systems(
+        schedule,
+        (
+            send_room_resets::<S>.in_set(ServerSimulationSystems::SendResets),
+            change_room_tick_rates.in_set(ServerSimulationSystems::QueueUpdates),
+            run_rooms.in_set(StepSimulationSystems),
+        ),
+    );
+}
+
+/// A separate server simulation that clients can be assigned to with [`InRoom`].
+///
+/// Derefs to the [`World`] of the room's simulation.
+#[derive(Component, Deref, DerefMut)]
+#[require(RoomClients)]
+pub struct SimulationRoom {
+    #[deref]
+    world: SimulationWorld,
+    overstep: Duration,
+}
+
+impl SimulationRoom {
+    pub fn new<S>() -> Self
+    where
+        S: PredictionScheme,
+    {
+        let mut app = App::empty();
+
+        app.add_schedule(Schedule::new(Main));
+
+        app.add_plugins(SimulationPlugin::<S> {
+            _p: PhantomData,
+            schedule: Main.intern(),
+            instance: SimulationInstance::Room,
+        });
+
+        SimulationRoom {
+            world: SimulationWorld::build(app),
+            overstep: Duration::ZERO,
+        }
+    }
+
+    /// The next tick that the room will execute.
+    pub fn current_tick(&self) -> SimulationTick {
+        self.world.resource::<Time<SimulationTime>>().current_tick()
+    }
+
+    pub fn tick_rate(&self) -> SimulationTickRate {
+        self.world.resource::<Time<SimulationTime>>().tick_rate()
+    }
+
+    /// Queues a world update to be applied to the room's simulation.
+    pub fn queue_update<T>(&mut self, update: WorldUpdate<T>)
+    where
+        T: Send + Sync + 'static,
+    {
+        self.world
+            .resource_mut::<UpdateExecutionQueue<T>>()
+            .insert(update);
+    }
+}
+
+/// Assigns a client to a [`SimulationRoom`].
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+#[relationship(relationship_target = RoomClients)]
+pub struct InRoom(pub Entity);
+
+/// The clients assigned to a [`SimulationRoom`].
+#[derive(Component, Default, Debug, Deref)]
+#[relationship_target(relationship = InRoom)]
+pub struct RoomClients(Vec<Entity>);
+
+fn send_room_resets<S>(
+    mut commands: Commands,
+    client_q: Query<(Entity, &InRoom, Has<PredictionClient>), Changed<InRoom>>,
+    room_q: Query<&SimulationRoom>,
+    mut sessions: ResMut<PredictionSessions>,
+    registry: Res<SimulationRegistry>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
+) -> Result
+where
+    S: PredictionScheme,
+{
+    for (client_entity, &InRoom(room_entity), is_prediction_client) in &client_q {
+        if is_prediction_client {
+            error!(
+                "Client {} is in a room but is also a `PredictionClient` of the main simulation",
+                client_entity
+            );
+
+            continue;
+        }
+
+        let Ok(room) = room_q.get(room_entity) else {
+            error!(
+                "Client {} is in room {} which doesn't have a `SimulationRoom`",
+                client_entity, room_entity
+            );
+
+            continue;
+        };
+
+        debug!(
+            "Resetting client {} to room {} on {:?}",
+            client_entity,
+            room_entity,
+            room.current_tick()
+        );
+
+        let session = sessions.untracked();
+
+        commands
+            .entity(client_entity)
+            .insert(PredictionSession(session));
+
+        messages.write(
+            client_entity,
+            true,
+            &ResetClientSimulation {
+                simulation_tick: room.current_tick(),
+                session,
+                snapshot: false,
+                fingerprint: registry.fingerprint::<S>(),
+                tick_rate: room.tick_rate(),
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Changes the tick rate of every room on its current tick, and sends the change to the room's clients.
+fn change_room_tick_rates(
+    mut changes: MessageReader<ChangeTickRate>,
+    mut room_q: Query<(&mut SimulationRoom, &RoomClients)>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
+) -> Result {
+    for &ChangeTickRate { interval } in changes.read() {
+        // Zero intervals are reported when the main simulation ignores them.
+        if interval.is_zero() {
+            continue;
+        }
+
+        for (mut room, clients) in &mut room_q {
+            let update = WorldUpdate {
+                tick: room.current_tick(),
+                update: TickRateUpdate { interval },
+            };
+
+            room.queue_update(update.clone());
+
+            for client_entity in clients.iter() {
+                messages.write(
+                    client_entity,
+                    true,
+                    &ServerWorldUpdate {
+                        update: update.clone(),
+                        include_in_prediction: true,
+                    },
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Advances every room with the [`PredictionClock`] and sends each tick to the room's clients.
+///
+/// Rooms are scaled by the same [`SimulationTimeScale`] as the main simulation.
+fn run_rooms(
+    mut room_q: Query<(Entity, &mut SimulationRoom, &RoomClients)>,
+    clock: Res<Time<PredictionClock>>,
+    time_scale: Res<SimulationTimeScale>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
+    mut late_updates: MessageWriter<LateWorldUpdate>,
+) -> Result {
+    for (room_entity, mut room, clients) in &mut room_q {
+        room.overstep += clock.delta().mul_f32(time_scale.effective());
+
+        loop {
+            let interval = room.tick_rate().interval;
+
+            if interval.is_zero() {
+                warn!(
+                    "Room {} has a zero tick interval and won't be advanced",
+                    room_entity
+                );
+
+                room.overstep = Duration::ZERO;
+                break;
+            }
+
+            if room.overstep < interval {
+                break;
+            }
+            room.overstep -= interval;
+
+            let executed_tick = room.current_tick();
+            room.world.run(1);
+            late_updates.write_batch(room.world.drain_late_updates());
+
+            for client_entity in clients.iter() {
+                messages.write(
+                    client_entity,
+                    true,
+                    &UpdateServerTick {
+                        simulation_tick: executed_tick,
+                    },
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,321 @@
+//! Prediction sessions allow a client that reconnects to resume from its last known tick instead of resetting.
+//!
+//! Every [`ResetClientSimulation`](crate::common::ResetClientSimulation) starts a new session with a [`SessionToken`].
+//! When a client receives a reset on a new connection it replies with the token of its previous session
+//! and the tick its template world has reached.
+//! If the previous session is still resumable, the server keeps the client's simulation and only resends
+//! the set of simulation entities and the components registered with a
+//! [`ResyncComponentPlugin`](crate::common::simulation::resync::ResyncComponentPlugin) that changed since that tick.
+//!
+//! Only the registered components, relations and resources can be resent.
+//! If the server applied any other world update, or removed a registered component from a simulation entity,
+//! on or after the client's tick, the client would miss it, so the session isn't resumed and the client resets instead.
+
+use std::{
+    any::TypeId,
+    hash::{BuildHasher, RandomState},
+};
+
+use bevy::{
+    ecs::system::SystemParam,
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    common::{
+        ResumeSession, ResumeSessionResult, SessionToken,
+        loopback::{PredictionReceiver, PredictionSender},
+        simulation::{
+            SimulationTick, SimulationTime, SimulationTimeExt, TickRateUpdate,
+            UpdateExecutionQueue, resync::ResyncSimulationEntities, schedules::SimulationPreUpdate,
+        },
+    },
+    server::{
+        PredictionClient, ServerPredictionSchedule, ServerSimulationSystems,
+        SimulationUpdatesStream,
+        resync::{PendingResync, PendingResyncs, ServerResyncSystems},
+        snapshot::{InitialSnapshot, InitialSnapshotSettings},
+    },
+};
+
+pub(crate) fn build(app: &mut App) {
+    let schedule = **app.world().resource::<ServerPredictionSchedule>();
+
+    app.init_resource::<PredictionSessions>();
+    app.init_resource::<SessionResumeWindow>();
+    app.init_resource::<UnresyncableChanges>();
+
+    // The tick rate is sent in the resume result.
+    register_resynced_update::<ResyncSimulationEntities>(app);
+    register_resynced_update::<TickRateUpdate>(app);
+
+    app.add_systems(
+        schedule,
+        (
+            track_disconnected_sessions.in_set(ServerSimulationSystems::SendResets),
+            receive_resume_requests.in_set(ServerResyncSystems::ReceiveRequests),
+        ),
+    );
+}
+
+/// How many ticks a disconnected session can be resumed for.
+#[derive(Resource, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct SessionResumeWindow(pub u32);
+
+impl Default for SessionResumeWindow {
+    fn default() -> Self {
+        SessionResumeWindow(600)
+    }
+}
+
+/// The session of a [`PredictionClient`].
+#[derive(Component, Clone, Copy, Debug, Deref)]
+pub struct PredictionSession(pub SessionToken);
+
+/// Inserted onto a [`PredictionClient`] when it resumes a previous session.
+///
+/// Logic that sends initial state to new clients can check for this component,
+/// because a resumed client already has its simulation and will be sent what changed.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ResumedSession {
+    pub previous_session: SessionToken,
+}
+
+struct SessionState {
+    client_entity: Entity,
+    disconnected_tick: Option<SimulationTick>,
+}
+
+/// Tracks the sessions that can be resumed.
+#[derive(Resource, Default)]
+pub(crate) struct PredictionSessions {
+    random_state: RandomState,
+    next_id: u64,
+    sessions: HashMap<SessionToken, SessionState>,
+}
+
+impl PredictionSessions {
+    /// Creates a new session for a client.
+    pub fn issue(&mut self, client_entity: Entity) -> SessionToken {
+        let token = self.untracked();
+
+        self.sessions.insert(
+            token,
+            SessionState {
+                client_entity,
+                disconnected_tick: None,
+            },
+        );
+
+        token
+    }
+
+    /// Creates a session token that can't be resumed.
+    pub fn untracked(&mut self) -> SessionToken {
+        let token = SessionToken(self.random_state.hash_one(self.next_id));
+        self.next_id += 1;
+
+        token
+    }
+}
+
+/// Is called on the server app for each world update added by the prediction scheme.
+pub(crate) fn build_update<T>(app: &mut App)
+where
+    T: Send + Sync + 'static,
+{
+    app.add_systems(SimulationPreUpdate, track_unresyncable_updates::<T>);
+}
+
+/// World update types that a resumed session is resynced with.
+#[derive(Resource, Default)]
+pub(crate) struct ResyncedUpdateTypes(HashSet<TypeId>);
+
+impl ResyncedUpdateTypes {
+    pub fn contains<T>(&self) -> bool
+    where
+        T: 'static,
+    {
+        self.0.contains(&TypeId::of::<T>())
+    }
+}
+
+/// Records that world updates of type `T` are resent when a session is resumed.
+pub(crate) fn register_resynced_update<T>(app: &mut App)
+where
+    T: 'static,
+{
+    app.world_mut()
+        .get_resource_or_init::<ResyncedUpdateTypes>()
+        .0
+        .insert(TypeId::of::<T>());
+}
+
+/// The latest tick of a change that resumed sessions can't be resynced with.
+#[derive(Resource, Default)]
+pub(crate) struct UnresyncableChanges {
+    latest: Option<SimulationTick>,
+}
+
+impl UnresyncableChanges {
+    pub fn record(&mut self, tick: SimulationTick) {
+        self.latest = self.latest.max(Some(tick));
+    }
+
+    fn since(&self, tick: SimulationTick) -> bool {
+        self.latest.is_some_and(|latest| latest >= tick)
+    }
+}
+
+/// Records the tick of world updates that are about to be applied if resumed sessions can't be resynced with them.
+fn track_unresyncable_updates<T>(
+    queue: Res<UpdateExecutionQueue<T>>,
+    resynced_types: Res<ResyncedUpdateTypes>,
+    mut unresyncable: ResMut<UnresyncableChanges>,
+    time: Res<Time<SimulationTime>>,
+) where
+    T: Send + Sync + 'static,
+{
+    if resynced_types.contains::<T>() {
+        return;
+    }
+
+    if queue
+        .front()
+        .is_some_and(|update| update.tick <= time.current_tick())
+    {
+        unresyncable.record(time.current_tick());
+    }
+}
+
+fn track_disconnected_sessions(
+    mut sessions: ResMut<PredictionSessions>,
+    mut removed_clients: RemovedComponents<PredictionClient>,
+    window: Res<SessionResumeWindow>,
+    time: Res<Time<SimulationTime>>,
+) {
+    let current_tick = time.current_tick();
+
+    for client_entity in removed_clients.read() {
+        for session in sessions.sessions.values_mut() {
+            if session.client_entity == client_entity && session.disconnected_tick.is_none() {
+                session.disconnected_tick = Some(current_tick);
+            }
+        }
+    }
+
+    sessions.sessions.retain(|_, session| {
+        session
+            .disconnected_tick
+            .is_none_or(|tick| current_tick.saturating_sub(*tick) <= **window)
+    });
+}
+
+/// Decides whether a disconnected session can be resumed from a tick.
+#[derive(SystemParam)]
+struct ResumeConditions<'w> {
+    window: Res<'w, SessionResumeWindow>,
+    unresyncable: Res<'w, UnresyncableChanges>,
+    time: Res<'w, Time<SimulationTime>>,
+}
+
+impl ResumeConditions<'_> {
+    fn can_resume_from(&self, tick: SimulationTick) -> bool {
+        let current_tick = self.time.current_tick();
+
+        tick <= current_tick
+            && current_tick.saturating_sub(*tick) <= **self.window
+            && !self.unresyncable.since(tick)
+    }
+}
+
+fn receive_resume_requests(
+    mut commands: Commands,
+    mut received: PredictionReceiver<ResumeSession, PredictionClient>,
+    mut sessions: ResMut<PredictionSessions>,
+    mut pending_resyncs: ResMut<PendingResyncs>,
+    conditions: ResumeConditions,
+    snapshot_settings: Res<InitialSnapshotSettings>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
+) -> Result {
+    let current_tick = conditions.time.current_tick();
+
+    for (
+        client_entity,
+        is_client,
+        ResumeSession {
+            previous_session,
+            tick,
+        },
+    ) in received.drain()
+    {
+        if !is_client {
+            warn!(
+                "Received a resume request from a connection that isn't a prediction client: {}",
+                client_entity
+            );
+
+            continue;
+        }
+
+        // Only sessions whose client has disconnected can be resumed, a connected client can't take over another's session.
+        let disconnected = sessions
+            .sessions
+            .get(&previous_session)
+            .is_some_and(|session| session.disconnected_tick.is_some());
+
+        if disconnected {
+            sessions.sessions.remove(&previous_session);
+        }
+
+        let resumable = disconnected && conditions.can_resume_from(tick);
+
+        messages.write(
+            client_entity,
+            true,
+            &ResumeSessionResult {
+                accepted: resumable,
+                simulation_tick: current_tick,
+                snapshot: snapshot_settings.enabled,
+                tick_rate: conditions.time.tick_rate(),
+            },
+        )?;
+
+        if !resumable {
+            debug!(
+                "Client {} could not resume session {:?} from {:?}",
+                client_entity, previous_session, tick
+            );
+
+            // The client resets, so any snapshot that was in progress needs to start over.
+            if snapshot_settings.enabled {
+                commands
+                    .entity(client_entity)
+                    .insert(InitialSnapshot::default());
+            }
+
+            continue;
+        }
+
+        debug!(
+            "Client {} resumed session {:?} from {:?}",
+            client_entity, previous_session, tick
+        );
+
+        commands
+            .entity(client_entity)
+            .insert(ResumedSession { previous_session })
+            .remove::<InitialSnapshot>();
+
+        pending_resyncs.push(PendingResync {
+            client_entity,
+            entities: None,
+            since: Some(tick),
+            resources: true,
+        });
+    }
+
+    Ok(())
+}
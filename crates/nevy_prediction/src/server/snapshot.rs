@@ -0,0 +1,145 @@
+//! Sends new clients a snapshot of the whole simulation so that they don't need to be initialized manually.
+//!
+//! When enabled with [`NevyPredictionServerPlugin::with_initial_snapshot`](crate::server::NevyPredictionServerPlugin::with_initial_snapshot),
+//! every new [`PredictionClient`] is sent the set of simulation entities right after its
+//! [`ResetClientSimulation`](crate::common::ResetClientSimulation),
+//! followed by everything registered with the [`resync`](crate::common::simulation::resync) plugins.
+//! Components and relations are sent in chunks of [`InitialSnapshotSettings::chunk_size`] entities per frame,
+//! and resources are sent with the last chunk.
+//! The client holds off prediction until the snapshot is complete.
+
+use bevy::prelude::*;
+use tracing::debug;
+
+use crate::{
+    common::{
+        SnapshotComplete,
+        loopback::PredictionSender,
+        simulation::{
+            SimulationTime, SimulationTimeExt, resync::ResyncSimulationEntities,
+            simulation_entity::SimulationEntity,
+        },
+    },
+    server::{
+        PredictionClient, ServerPredictionSchedule, ServerSimulationSystems,
+        SimulationUpdatesStream, WorldUpdateSender,
+        resync::{PendingResync, PendingResyncs, ServerResyncSystems},
+    },
+};
+
+pub(crate) fn build(app: &mut App, enabled: bool) {
+    let schedule = **app.world().resource::<ServerPredictionSchedule>();
+
+    app.insert_resource(InitialSnapshotSettings {
+        enabled,
+        ..default()
+    });
+
+    app.add_systems(
+        schedule,
+        (
+            queue_initial_snapshots
+                .in_set(ServerSimulationSystems::SendResyncs)
+                .after(ServerResyncSystems::ReceiveRequests)
+                .before(ServerResyncSystems::SendEntities),
+            send_snapshot_completions.in_set(ServerResyncSystems::Clear),
+        ),
+    );
+}
+
+/// Controls the initial snapshots sent to new clients.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct InitialSnapshotSettings {
+    /// Whether new clients are sent an initial snapshot.
+    pub enabled: bool,
+    /// How many simulation entities are sent to a client each frame.
+    pub chunk_size: usize,
+}
+
+impl Default for InitialSnapshotSettings {
+    fn default() -> Self {
+        InitialSnapshotSettings {
+            enabled: false,
+            chunk_size: 256,
+        }
+    }
+}
+
+/// Inserted onto a [`PredictionClient`] while it is being sent its initial snapshot.
+#[derive(Component, Default)]
+pub struct InitialSnapshot {
+    /// The entities that haven't been sent yet, or `None` if the snapshot hasn't started.
+    remaining: Option<Vec<SimulationEntity>>,
+}
+
+fn queue_initial_snapshots(
+    mut client_q: Query<(Entity, &mut InitialSnapshot), With<PredictionClient>>,
+    entity_q: Query<&SimulationEntity>,
+    settings: Res<InitialSnapshotSettings>,
+    mut pending: ResMut<PendingResyncs>,
+    mut sender: WorldUpdateSender,
+) -> Result {
+    for (client_entity, mut snapshot) in &mut client_q {
+        let remaining = match &mut snapshot.remaining {
+            Some(remaining) => remaining,
+            None => {
+                let entities: Vec<_> = entity_q.iter().copied().collect();
+
+                debug!(
+                    "Sending an initial snapshot of {} entities to client {}",
+                    entities.len(),
+                    client_entity
+                );
+
+                sender.write_now(
+                    client_entity,
+                    true,
+                    ResyncSimulationEntities {
+                        entities: entities.clone(),
+                        scope: None,
+                    },
+                )?;
+
+                snapshot.remaining.insert(entities)
+            }
+        };
+
+        let chunk_start = remaining.len().saturating_sub(settings.chunk_size.max(1));
+        let chunk = remaining.split_off(chunk_start);
+
+        pending.push(PendingResync {
+            client_entity,
+            entities: Some(chunk.into_iter().collect()),
+            since: None,
+            resources: remaining.is_empty(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Informs clients that their snapshot is complete once the last chunk has been sent.
+fn send_snapshot_completions(
+    mut commands: Commands,
+    client_q: Query<(Entity, &InitialSnapshot)>,
+    time: Res<Time<SimulationTime>>,
+    mut messages: PredictionSender<SimulationUpdatesStream>,
+) -> Result {
+    for (client_entity, snapshot) in &client_q {
+        if !snapshot.remaining.as_ref().is_some_and(Vec::is_empty) {
+            continue;
+        }
+
+        messages.write(
+            client_entity,
+            true,
+            &SnapshotComplete {
+                simulation_tick: time.current_tick(),
+            },
+        )?;
+
+        commands.entity(client_entity).remove::<InitialSnapshot>();
+    }
+
+    Ok(())
+}
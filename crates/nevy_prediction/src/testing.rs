@@ -0,0 +1,347 @@
+//! Utilities for testing prediction schemes without networking.
+//!
+//! [`PredictionTestHarness`] builds a server app and any number of client apps for a [`PredictionScheme`],
+//! connects them with [`LoopbackLink`]s and updates them with a fixed frame interval instead of the wall clock.
+//! This makes tests deterministic and lets them run faster than real time.
+//!
+//! This module requires the `testing` feature.
+//!
+//! ```ignore
+//! let mut harness = PredictionTestHarness::<MyScheme>::builder(2)
+//!     .with_setup(|app| {
+//!         app.add_plugins(NevyPlugins::default());
+//!         app.init_protocol::<()>();
+//!     })
+//!     .with_server_setup(|app| {
+//!         app.include_protocol::<(), PredictionMessages>();
+//!     })
+//!     .with_client_setup(|app, _| {
+//!         app.include_protocol::<(), PredictionMessages>();
+//!     })
+//!     .build();
+//!
+//! harness.run_until_synchronized(SimulationTick(100), 1000)?;
+//! harness.assert_template_matches_server(0, SimulationTick(100));
+//! ```
+
+use std::{fmt::Debug, marker::PhantomData, time::Duration};
+
+use bevy::{app::PluginsState, prelude::*, time::TimeUpdateStrategy};
+
+use crate::{
+    client::{NevyPredictionClientPlugin, PredictionInterval, template_world::TemplateWorld},
+    common::{
+        loopback::{LoopbackConditions, LoopbackLink},
+        scheme::PredictionScheme,
+        simulation::{
+            SimulationTick, SimulationTime, SimulationTimeExt, checksum::SimulationChecksums,
+            simulation_entity::SimulationEntity,
+        },
+    },
+    server::NevyPredictionServerPlugin,
+};
+
+type ClientSetup = Box<dyn Fn(&mut App, usize)>;
+
+/// Builds a [`PredictionTestHarness`].
+pub struct PredictionTestHarnessBuilder<S> {
+    clients: usize,
+    frame_interval: Duration,
+    conditions: LoopbackConditions,
+    server_plugin: NevyPredictionServerPlugin<S>,
+    setup: Box<dyn Fn(&mut App)>,
+    server_setup: Box<dyn Fn(&mut App)>,
+    client_setup: ClientSetup,
+}
+
+impl<S> PredictionTestHarnessBuilder<S>
+where
+    S: PredictionScheme,
+{
+    /// How much time passes on every app each frame.
+    ///
+    /// Defaults to the step interval of the scheme.
+    pub fn with_frame_interval(mut self, frame_interval: Duration) -> Self {
+        self.frame_interval = frame_interval;
+        self
+    }
+
+    /// The network conditions between the server and each client.
+    pub fn with_conditions(mut self, conditions: LoopbackConditions) -> Self {
+        self.conditions = conditions;
+        self
+    }
+
+    /// Uses a configured server plugin instead of the default one.
+    ///
+    /// The schedule of the plugin should be [`Update`].
+    pub fn with_server_plugin(mut self, plugin: NevyPredictionServerPlugin<S>) -> Self {
+        self.server_plugin = plugin;
+        self
+    }
+
+    /// Runs on every app before the prediction plugins are added.
+    ///
+    /// This is where the nevy plugins and protocol should be added.
+    pub fn with_setup(mut self, setup: impl Fn(&mut App) + 'static) -> Self {
+        self.setup = Box::new(setup);
+        self
+    }
+
+    /// Runs on the server app after the prediction plugin is added.
+    pub fn with_server_setup(mut self, setup: impl Fn(&mut App) + 'static) -> Self {
+        self.server_setup = Box::new(setup);
+        self
+    }
+
+    /// Runs on each client app after the prediction plugin is added, with the index of the client.
+    ///
+    /// Client apps start with a zero [`PredictionInterval`], which can be changed here.
+    pub fn with_client_setup(mut self, setup: impl Fn(&mut App, usize) + 'static) -> Self {
+        self.client_setup = Box::new(setup);
+        self
+    }
+
+    /// Builds the apps and connects every client to the server.
+    pub fn build(mut self) -> PredictionTestHarness<S> {
+        let server_plugin = std::mem::take(&mut self.server_plugin);
+
+        let mut server = self.new_app();
+        server.add_plugins(server_plugin);
+        (self.server_setup)(&mut server);
+        finish_app(&mut server);
+
+        let mut clients = Vec::with_capacity(self.clients);
+
+        for index in 0..self.clients {
+            let mut app = self.new_app();
+            app.add_plugins(NevyPredictionClientPlugin::<S>::default());
+            app.init_resource::<PredictionInterval>();
+            (self.client_setup)(&mut app, index);
+            finish_app(&mut app);
+
+            let link = LoopbackLink::new(LoopbackConditions {
+                seed: self.conditions.seed.wrapping_add(index as u64),
+                ..self.conditions.clone()
+            });
+
+            let (client_entity, server_entity) = link.connect(server.world_mut(), app.world_mut());
+
+            clients.push(TestClient {
+                app,
+                link,
+                client_entity,
+                server_entity,
+            });
+        }
+
+        PredictionTestHarness {
+            server,
+            clients,
+            _p: PhantomData,
+        }
+    }
+
+    fn new_app(&self) -> App {
+        let mut app = App::new();
+
+        app.add_plugins(MinimalPlugins);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(self.frame_interval));
+
+        (self.setup)(&mut app);
+
+        app
+    }
+}
+
+fn finish_app(app: &mut App) {
+    while let PluginsState::Adding = app.plugins_state() {
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+}
+
+/// A client app in a [`PredictionTestHarness`].
+pub struct TestClient {
+    pub app: App,
+    /// The link to the server, which can be used to change its conditions during a test.
+    pub link: LoopbackLink,
+    /// The entity of this client in the server app.
+    pub client_entity: Entity,
+    /// The entity of the server in this client app.
+    pub server_entity: Entity,
+}
+
+impl TestClient {
+    /// The [`TemplateWorld`] of this client.
+    pub fn template_world(&mut self) -> &mut World {
+        self.app
+            .world_mut()
+            .resource_mut::<TemplateWorld>()
+            .into_inner()
+    }
+
+    /// The current tick of the template world, which is the next tick it will execute.
+    pub fn template_tick(&self) -> SimulationTick {
+        self.app
+            .world()
+            .resource::<TemplateWorld>()
+            .resource::<Time<SimulationTime>>()
+            .current_tick()
+    }
+}
+
+/// A server app and client apps connected with [`LoopbackLink`]s.
+///
+/// See the [module level docs](self).
+pub struct PredictionTestHarness<S> {
+    pub server: App,
+    pub clients: Vec<TestClient>,
+    _p: PhantomData<S>,
+}
+
+impl<S> PredictionTestHarness<S>
+where
+    S: PredictionScheme,
+{
+    /// Creates a builder for a harness with some number of clients.
+    pub fn builder(clients: usize) -> PredictionTestHarnessBuilder<S> {
+        PredictionTestHarnessBuilder {
+            clients,
+            frame_interval: S::step_interval(),
+            conditions: default(),
+            server_plugin: NevyPredictionServerPlugin::new(Update),
+            setup: Box::new(|_| ()),
+            server_setup: Box::new(|_| ()),
+            client_setup: Box::new(|_, _| ()),
+        }
+    }
+
+    /// Updates the server and then every client once.
+    pub fn update(&mut self) {
+        self.server.update();
+
+        for client in &mut self.clients {
+            client.app.update();
+        }
+    }
+
+    /// Updates every app some number of times.
+    pub fn update_frames(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.update();
+        }
+    }
+
+    /// Updates every app until `condition` returns `true`.
+    ///
+    /// Returns an error if the condition wasn't met within `max_frames`.
+    pub fn run_until(
+        &mut self,
+        max_frames: u32,
+        mut condition: impl FnMut(&mut Self) -> bool,
+    ) -> Result {
+        for _ in 0..max_frames {
+            if condition(self) {
+                return Ok(());
+            }
+
+            self.update();
+        }
+
+        if condition(self) {
+            return Ok(());
+        }
+
+        Err(format!("Condition wasn't met within {} frames", max_frames).into())
+    }
+
+    /// Updates every app until the server and the template world of every client have executed `tick`.
+    pub fn run_until_synchronized(&mut self, tick: SimulationTick, max_frames: u32) -> Result {
+        self.run_until(max_frames, |harness| {
+            harness.server_tick() > tick
+                && harness
+                    .clients
+                    .iter()
+                    .all(|client| client.template_tick() > tick)
+        })
+    }
+
+    /// The current tick of the server, which is the next tick it will execute.
+    pub fn server_tick(&self) -> SimulationTick {
+        self.server
+            .world()
+            .resource::<Time<SimulationTime>>()
+            .current_tick()
+    }
+
+    /// Asserts that the template world of a client had the same state as the server at a tick.
+    ///
+    /// State is compared using the [`checksum`](crate::common::simulation::checksum) plugins,
+    /// so the scheme must register the types to compare for checksums.
+    /// Both instances must have executed the tick recently enough for it to still be in their [`SimulationChecksums`].
+    pub fn assert_template_matches_server(&mut self, client: usize, tick: SimulationTick) {
+        let server_checksums = self
+            .server
+            .world()
+            .resource::<SimulationChecksums>()
+            .get(tick)
+            .unwrap_or_else(|| panic!("The server has no checksums for {:?}", tick))
+            .clone();
+
+        let client_checksums = self.clients[client]
+            .app
+            .world()
+            .resource::<TemplateWorld>()
+            .resource::<SimulationChecksums>()
+            .get(tick)
+            .unwrap_or_else(|| panic!("Client {} has no checksums for {:?}", client, tick))
+            .clone();
+
+        assert_eq!(
+            server_checksums, client_checksums,
+            "The template world of client {} doesn't match the server at {:?}",
+            client, tick
+        );
+    }
+
+    /// Asserts that the predicted state of a component in a client's main world
+    /// is the same as the state in its template world.
+    ///
+    /// Prediction runs ahead of the template world,
+    /// so this only holds once the component has stopped changing, e.g. after inputs have stopped.
+    pub fn assert_prediction_converged<C>(&mut self, client: usize)
+    where
+        C: Component + Clone + PartialEq + Debug,
+    {
+        let test_client = &mut self.clients[client];
+
+        let predicted = simulation_components::<C>(test_client.app.world_mut());
+        let template = simulation_components::<C>(test_client.template_world());
+
+        assert_eq!(
+            predicted,
+            template,
+            "The prediction of `{}` on client {} hasn't converged with the template world",
+            std::any::type_name::<C>(),
+            client
+        );
+    }
+}
+
+/// Collects a component from every [`SimulationEntity`] in a world, sorted by simulation entity.
+pub fn simulation_components<C>(world: &mut World) -> Vec<(SimulationEntity, C)>
+where
+    C: Component + Clone,
+{
+    let mut components: Vec<_> = world
+        .query::<(&SimulationEntity, &C)>()
+        .iter(world)
+        .map(|(&simulation_entity, component)| (simulation_entity, component.clone()))
+        .collect();
+
+    components.sort_unstable_by_key(|(simulation_entity, _)| simulation_entity.0);
+
+    components
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use nevy_prediction::{prelude::*, testing::PredictionTestHarness};
+
+#[derive(Resource, Default)]
+struct Desyncs(Vec<SimulationDesync>);
+
+fn count_desyncs(mut desyncs: MessageReader<SimulationDesync>, mut counted: ResMut<Desyncs>) {
+    counted.0.extend(desyncs.read().cloned());
+}
+
+fn checksum_harness(auto_resync: bool) -> PredictionTestHarness<TestScheme> {
+    let mut harness = harness(1);
+
+    harness.server.insert_resource(ChecksumInterval(1));
+
+    let client = &mut harness.clients[0].app;
+    client.insert_resource(AutoResync(auto_resync));
+    client.init_resource::<Desyncs>();
+    client.add_systems(Last, count_desyncs);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    harness
+}
+
+fn desyncs(harness: &PredictionTestHarness<TestScheme>) -> &[SimulationDesync] {
+    &harness.clients[0].app.world().resource::<Desyncs>().0
+}
+
+#[test]
+fn synchronized_clients_dont_desync() {
+    let mut harness = checksum_harness(false);
+
+    harness.update_frames(20);
+
+    assert!(desyncs(&harness).is_empty());
+}
+
+#[test]
+fn diverging_state_is_detected() {
+    let mut harness = checksum_harness(false);
+
+    spawn_unsent_mover(&mut harness, SimulationEntity(2));
+    let tick = harness.server_tick();
+    harness.update_frames(20);
+
+    let desyncs = desyncs(&harness);
+    assert!(!desyncs.is_empty(), "The desync wasn't detected");
+    assert!(desyncs.iter().all(|desync| desync.tick >= tick));
+}
+
+#[test]
+fn desyncs_are_resynced_automatically() {
+    let mut harness = checksum_harness(true);
+
+    spawn_unsent_mover(&mut harness, SimulationEntity(2));
+    harness.update_frames(20);
+    assert!(!desyncs(&harness).is_empty(), "The desync wasn't detected");
+
+    let tick = SimulationTick(*harness.server_tick() + 5);
+    harness.run_until_synchronized(tick, 200).unwrap();
+    harness.assert_template_matches_server(0, tick);
+}
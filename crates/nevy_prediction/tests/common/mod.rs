@@ -0,0 +1,249 @@
+//! A small prediction scheme shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*, time::TimeUpdateStrategy};
+use nevy::prelude::*;
+use nevy_prediction::{prelude::*, testing::PredictionTestHarness};
+use serde::{Deserialize, Serialize};
+
+pub struct TestScheme;
+
+impl PredictionScheme for TestScheme {
+    fn plugin() -> impl Plugin {
+        TestSimulationPlugin
+    }
+
+    fn step_interval() -> Duration {
+        Duration::from_millis(50)
+    }
+}
+
+pub struct TestSimulationPlugin;
+
+/// A seed that differs from the default so that tests catch instances that ignore it.
+pub const TEST_SEED: u64 = 0x1234_5678_9abc_def0;
+
+impl Plugin for TestSimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SimulationRngSeed(TEST_SEED));
+        app.init_resource::<RandomDraws>();
+
+        app.add_world_update::<SpawnMover>();
+        // Both orders of resync and update plugins are supported.
+        app.add_plugins(UpdateComponentPlugin::<Velocity>::default());
+        app.add_plugins(ResyncComponentPlugin::<Velocity>::default());
+        app.add_plugins(ResyncComponentPlugin::<Position>::default());
+        app.add_plugins(UpdateComponentPlugin::<Position>::default());
+
+        app.add_plugins(ExtractSimulationComponentPlugin::<Position>::default());
+        app.add_plugins(ExtractSimulationComponentPlugin::<Velocity>::default());
+
+        app.add_plugins(ChecksumComponentPlugin::<Position>::default());
+        app.add_plugins(ChecksumComponentPlugin::<Velocity>::default());
+
+        app.add_systems(
+            SimulationUpdate,
+            (
+                spawn_movers.before(UpdateComponentSystems),
+                move_movers.after(UpdateComponentSystems),
+                draw_random,
+            ),
+        );
+    }
+}
+
+/// The [`TestScheme`] in [`SynchronizationMode::Lockstep`], where clients can set the velocity of movers.
+pub struct LockstepScheme;
+
+impl PredictionScheme for LockstepScheme {
+    fn plugin() -> impl Plugin {
+        |app: &mut App| {
+            app.add_plugins(TestSimulationPlugin);
+            app.add_client_world_update::<SetVelocity>();
+            app.add_systems(
+                SimulationUpdate,
+                set_velocities.before(UpdateComponentSystems),
+            );
+        }
+    }
+
+    fn step_interval() -> Duration {
+        TestScheme::step_interval()
+    }
+
+    fn synchronization_mode() -> SynchronizationMode {
+        SynchronizationMode::Lockstep
+    }
+}
+
+/// A world update created by clients of the [`LockstepScheme`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SetVelocity {
+    pub entity: SimulationEntity,
+    pub velocity: Velocity,
+}
+
+fn set_velocities(
+    map: Res<SimulationEntityMap>,
+    mut velocity_q: Query<&mut Velocity>,
+    mut updates: ReadyUpdates<SetVelocity>,
+) {
+    for SetVelocity { entity, velocity } in updates.drain() {
+        if let Some(mut current) = map
+            .get(entity)
+            .and_then(|entity| velocity_q.get_mut(entity).ok())
+        {
+            *current = velocity;
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Position(pub i64);
+
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Velocity(pub i64);
+
+/// Spawns a simulation entity that moves by its [`Velocity`] every tick.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpawnMover {
+    pub entity: SimulationEntity,
+}
+
+fn spawn_movers(mut commands: Commands, mut updates: ReadyUpdates<SpawnMover>) {
+    for SpawnMover { entity } in updates.drain() {
+        commands.spawn((entity, Position::default(), Velocity::default()));
+    }
+}
+
+fn move_movers(mut mover_q: Query<(&mut Position, &Velocity)>) {
+    for (mut position, velocity) in &mut mover_q {
+        position.0 += velocity.0;
+    }
+}
+
+/// A number drawn from the [`DeterministicRng`] on every tick that an instance executed.
+///
+/// This isn't part of the simulation state, so it records the draws of one instance.
+#[derive(Resource, Default)]
+pub struct RandomDraws(pub Vec<(SimulationTick, u64)>);
+
+fn draw_random(
+    rng: DeterministicRng,
+    time: Res<Time<SimulationTime>>,
+    mut draws: ResMut<RandomDraws>,
+) {
+    draws.0.push((time.current_tick(), rng.tick().next_u64()));
+}
+
+/// Builds a harness for the [`TestScheme`] with the nevy plugins and prediction protocol added to every app.
+pub fn harness(clients: usize) -> PredictionTestHarness<TestScheme> {
+    harness_builder(clients).build()
+}
+
+pub fn harness_builder(
+    clients: usize,
+) -> nevy_prediction::testing::PredictionTestHarnessBuilder<TestScheme> {
+    scheme_harness_builder(clients)
+}
+
+/// Builds a harness for any scheme with the nevy plugins and prediction protocol added to every app.
+pub fn scheme_harness_builder<S: PredictionScheme>(
+    clients: usize,
+) -> nevy_prediction::testing::PredictionTestHarnessBuilder<S> {
+    PredictionTestHarness::<S>::builder(clients)
+        .with_setup(|app| {
+            app.add_plugins(NevyPlugins::default());
+            app.init_protocol::<()>();
+        })
+        .with_server_setup(|app| {
+            app.include_protocol::<(), PredictionMessages>();
+        })
+        .with_client_setup(|app, _| {
+            app.include_protocol::<(), PredictionMessages>();
+        })
+}
+
+/// Applies a world update to the server's simulation on its current tick and sends it to every client.
+pub fn server_update<S, T>(
+    harness: &mut PredictionTestHarness<S>,
+    update: T,
+    include_in_prediction: bool,
+) where
+    S: PredictionScheme,
+    T: Serialize + Send + Sync + Clone + 'static,
+{
+    harness
+        .server
+        .world_mut()
+        .run_system_once::<_, Result, _>(
+            move |client_q: Query<Entity, With<PredictionClient>>,
+                  mut queue: ResMut<UpdateExecutionQueue<T>>,
+                  mut sender: WorldUpdateSender|
+                  -> Result {
+                let update = WorldUpdate {
+                    tick: sender.time.current_tick(),
+                    update: update.clone(),
+                };
+
+                queue.insert(update.clone());
+
+                for client_entity in &client_q {
+                    sender.write(client_entity, true, include_in_prediction, update.clone())?;
+                }
+
+                Ok(())
+            },
+        )
+        .unwrap()
+        .unwrap();
+}
+
+/// Spawns a mover on the server without telling the clients about it, which desyncs them.
+pub fn spawn_unsent_mover(
+    harness: &mut PredictionTestHarness<TestScheme>,
+    entity: SimulationEntity,
+) {
+    harness
+        .server
+        .world_mut()
+        .spawn((entity, Position(0), Velocity(1)));
+}
+
+/// Disconnects a client from the server.
+pub fn disconnect_client(harness: &mut PredictionTestHarness<TestScheme>, client: usize) {
+    let client = &mut harness.clients[client];
+    harness.server.world_mut().despawn(client.client_entity);
+    client.app.world_mut().despawn(client.server_entity);
+}
+
+/// Connects a client to the server on a new link.
+pub fn connect_client(harness: &mut PredictionTestHarness<TestScheme>, client: usize) {
+    let link = LoopbackLink::new(default());
+    let client = &mut harness.clients[client];
+    let (client_entity, server_entity) =
+        link.connect(harness.server.world_mut(), client.app.world_mut());
+    client.link = link;
+    client.client_entity = client_entity;
+    client.server_entity = server_entity;
+}
+
+/// Builds a client app for a scheme outside of a harness, with no server connection.
+pub fn client_app<S: PredictionScheme>() -> App {
+    let mut app = App::new();
+
+    app.add_plugins(MinimalPlugins);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(S::step_interval()));
+    app.add_plugins(NevyPlugins::default());
+    app.init_protocol::<()>();
+    app.add_plugins(NevyPredictionClientPlugin::<S>::default());
+    app.init_resource::<PredictionInterval>();
+    app.include_protocol::<(), PredictionMessages>();
+    app.finish();
+    app.cleanup();
+
+    app
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use bevy::diagnostic::DiagnosticsStore;
+use common::*;
+use nevy_prediction::{prelude::*, testing::PredictionTestHarness};
+
+fn estimated_bytes_sent<T>(harness: &PredictionTestHarness<TestScheme>) -> Option<f64> {
+    harness
+        .server
+        .world()
+        .resource::<DiagnosticsStore>()
+        .get(&PredictionDiagnosticsPlugin::estimated_bytes_sent(
+            std::any::type_name::<T>(),
+        ))
+        .and_then(|diagnostic| diagnostic.value())
+}
+
+#[test]
+fn estimated_bytes_sent_counts_sent_updates() {
+    let mut harness = harness_builder(1)
+        .with_server_setup(|app| {
+            app.add_plugins(PredictionDiagnosticsPlugin::default());
+        })
+        .build();
+
+    harness.update_frames(3);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+
+    harness.update();
+
+    let bytes = estimated_bytes_sent::<SpawnMover>(&harness)
+        .expect("The estimated bytes sent weren't measured");
+    assert!(bytes > 0., "No bytes were estimated for a sent update");
+
+    harness.update();
+
+    assert_eq!(
+        estimated_bytes_sent::<SpawnMover>(&harness),
+        Some(0.),
+        "A frame without updates should measure zero bytes"
+    );
+}
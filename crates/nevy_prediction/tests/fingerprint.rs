@@ -0,0 +1,73 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::*;
+use nevy_prediction::prelude::*;
+
+/// A scheme that adds different world updates than the [`TestScheme`].
+struct MismatchedScheme;
+
+impl PredictionScheme for MismatchedScheme {
+    fn plugin() -> impl Plugin {
+        |app: &mut App| {
+            app.add_world_update::<SpawnMover>();
+        }
+    }
+
+    fn step_interval() -> Duration {
+        TestScheme::step_interval()
+    }
+}
+
+#[derive(Resource, Default)]
+struct Mismatches(Vec<ProtocolMismatch>);
+
+fn count_mismatches(
+    mut mismatches: MessageReader<ProtocolMismatch>,
+    mut counted: ResMut<Mismatches>,
+) {
+    counted.0.extend(mismatches.read().copied());
+}
+
+#[test]
+fn clients_with_a_different_protocol_are_rejected() {
+    let mut harness = harness(1);
+
+    harness.server.init_resource::<Mismatches>();
+    harness.server.add_systems(Last, count_mismatches);
+
+    let mut client = client_app::<MismatchedScheme>();
+    client.init_resource::<Mismatches>();
+    client.add_systems(Last, count_mismatches);
+
+    let link = LoopbackLink::new(default());
+    let (client_entity, server_entity) =
+        link.connect(harness.server.world_mut(), client.world_mut());
+
+    for _ in 0..10 {
+        harness.update();
+        client.update();
+    }
+
+    let server_mismatches = &harness.server.world().resource::<Mismatches>().0;
+    assert_eq!(server_mismatches.len(), 1);
+    assert_eq!(server_mismatches[0].connection_entity, client_entity);
+
+    let client_mismatches = &client.world().resource::<Mismatches>().0;
+    assert_eq!(client_mismatches.len(), 1);
+    assert_eq!(client_mismatches[0].connection_entity, server_entity);
+
+    let server = harness.server.world();
+    assert!(
+        !server.entity(client_entity).contains::<PredictionClient>(),
+        "The server still sends world updates to the mismatched client"
+    );
+    assert!(
+        server
+            .entity(harness.clients[0].client_entity)
+            .contains::<PredictionClient>(),
+        "The server rejected a client with the same protocol"
+    );
+}
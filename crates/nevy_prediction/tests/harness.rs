@@ -0,0 +1,121 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::*;
+use nevy::prelude::*;
+use nevy_prediction::{prelude::*, testing::simulation_components};
+
+#[test]
+fn template_world_matches_server() {
+    let mut harness = harness(2);
+
+    harness.update_frames(5);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+
+    harness.update_frames(2);
+
+    server_update(
+        &mut harness,
+        UpdateComponent {
+            entity: SimulationEntity(1),
+            component: Velocity(3),
+        },
+        false,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 20);
+
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    harness.assert_template_matches_server(0, tick);
+    harness.assert_template_matches_server(1, tick);
+}
+
+#[test]
+fn prediction_converges_once_inputs_stop() {
+    prediction_converges(PredictionExecution::MainThread);
+}
+
+#[test]
+fn background_prediction_converges_once_inputs_stop() {
+    prediction_converges(PredictionExecution::Background);
+}
+
+fn prediction_converges(execution: PredictionExecution) {
+    let mut harness = harness_builder(1)
+        .with_conditions(LoopbackConditions {
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .with_client_setup(move |app, _| {
+            app.include_protocol::<(), PredictionMessages>();
+            app.insert_resource(PredictionInterval(Duration::from_millis(300)));
+            app.insert_resource(execution);
+        })
+        .build();
+
+    harness.update_frames(5);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        true,
+    );
+
+    for velocity in [2, 5, -1, 4] {
+        harness.update_frames(3);
+
+        server_update(
+            &mut harness,
+            UpdateComponent {
+                entity: SimulationEntity(1),
+                component: Velocity(velocity),
+            },
+            true,
+        );
+    }
+
+    harness.update_frames(3);
+
+    server_update(
+        &mut harness,
+        UpdateComponent {
+            entity: SimulationEntity(1),
+            component: Velocity(0),
+        },
+        true,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 20);
+
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    harness.assert_template_matches_server(0, tick);
+    let predicted = simulation_components::<Position>(harness.clients[0].app.world_mut());
+    assert_eq!(predicted.len(), 1, "The mover wasn't predicted");
+
+    let client = &harness.clients[0];
+    let predicted_tick = client
+        .app
+        .world()
+        .resource::<Time<SimulationTime>>()
+        .current_tick();
+    assert!(
+        predicted_tick > client.template_tick(),
+        "The main world isn't predicting ahead of the template world"
+    );
+
+    harness.assert_prediction_converged::<Velocity>(0);
+    harness.assert_prediction_converged::<Position>(0);
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use common::*;
+use nevy_prediction::{prelude::*, testing::PredictionTestHarness};
+
+#[derive(Resource, Default)]
+struct LateUpdates(Vec<LateWorldUpdate>);
+
+fn collect_late_updates(
+    mut late_updates: MessageReader<LateWorldUpdate>,
+    mut collected: ResMut<LateUpdates>,
+) {
+    collected.0.extend(late_updates.read().copied());
+}
+
+fn collect(app: &mut App) {
+    app.init_resource::<LateUpdates>();
+    app.add_systems(Last, collect_late_updates);
+}
+
+fn late_updates(app: &App) -> &[LateWorldUpdate] {
+    &app.world().resource::<LateUpdates>().0
+}
+
+fn synchronized_harness() -> PredictionTestHarness<TestScheme> {
+    let mut harness = harness(1);
+
+    collect(&mut harness.server);
+    collect(&mut harness.clients[0].app);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    harness
+}
+
+#[test]
+fn late_updates_on_the_server_are_reported() {
+    let mut harness = synchronized_harness();
+
+    let intended_tick = SimulationTick(*harness.server_tick() - 5);
+
+    harness
+        .server
+        .world_mut()
+        .resource_mut::<UpdateExecutionQueue<UpdateComponent<Velocity>>>()
+        .insert(WorldUpdate {
+            tick: intended_tick,
+            update: UpdateComponent {
+                entity: SimulationEntity(1),
+                component: Velocity(1),
+            },
+        });
+
+    harness.update_frames(2);
+
+    let late_updates = late_updates(&harness.server);
+    assert_eq!(late_updates.len(), 1);
+
+    let late_update = late_updates[0];
+    assert!(matches!(late_update.instance, SimulationInstance::Server));
+    assert_eq!(late_update.intended_tick, intended_tick);
+    assert_eq!(
+        late_update.lateness,
+        *late_update.applied_tick - *intended_tick
+    );
+    assert!(late_update.lateness >= 5);
+}
+
+#[test]
+fn late_updates_in_the_template_world_are_forwarded() {
+    let mut harness = synchronized_harness();
+
+    let intended_tick = SimulationTick(*harness.clients[0].template_tick() - 5);
+
+    harness
+        .server
+        .world_mut()
+        .run_system_once::<_, Result, _>(
+            move |client_q: Query<Entity, With<PredictionClient>>,
+                  mut sender: WorldUpdateSender|
+                  -> Result {
+                for client_entity in &client_q {
+                    sender.write(
+                        client_entity,
+                        true,
+                        false,
+                        WorldUpdate {
+                            tick: intended_tick,
+                            update: UpdateComponent {
+                                entity: SimulationEntity(1),
+                                component: Velocity(1),
+                            },
+                        },
+                    )?;
+                }
+
+                Ok(())
+            },
+        )
+        .unwrap()
+        .unwrap();
+
+    harness.update_frames(5);
+
+    let late_updates: Vec<_> = late_updates(&harness.clients[0].app)
+        .iter()
+        .filter(|late_update| matches!(late_update.instance, SimulationInstance::ClientTemplate))
+        .collect();
+    assert_eq!(late_updates.len(), 1);
+    assert_eq!(late_updates[0].intended_tick, intended_tick);
+}
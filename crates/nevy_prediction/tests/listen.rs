@@ -0,0 +1,71 @@
+mod common;
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use common::*;
+use nevy_prediction::{
+    prelude::*,
+    testing::{PredictionTestHarness, simulation_components},
+};
+
+/// Spawns a mover as the host of a listen server, returning the created update.
+fn host_spawn_mover<S: PredictionScheme>(
+    harness: &mut PredictionTestHarness<S>,
+    entity: SimulationEntity,
+) -> WorldUpdate<SpawnMover> {
+    harness
+        .server
+        .world_mut()
+        .run_system_once::<_, Result<WorldUpdate<SpawnMover>>, _>(
+            move |mut creator: HostUpdateCreator<SpawnMover>| creator.create(SpawnMover { entity }),
+        )
+        .unwrap()
+        .unwrap()
+}
+
+fn has_mover(world: &mut World, entity: SimulationEntity) -> bool {
+    simulation_components::<Position>(world)
+        .iter()
+        .any(|&(simulation_entity, _)| simulation_entity == entity)
+}
+
+#[test]
+fn host_updates_are_applied_on_the_server() {
+    let mut harness = harness(1);
+    harness.clients[0].app.insert_resource(AutoResync(false));
+
+    let tick = SimulationTick(*harness.server_tick() + 5);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    let update = host_spawn_mover(&mut harness, SimulationEntity(1));
+    assert_eq!(update.tick, harness.server_tick());
+
+    harness.update_frames(10);
+
+    assert!(has_mover(harness.server.world_mut(), SimulationEntity(1)));
+
+    // In state synchronization the host's updates aren't sent to clients.
+    assert!(!has_mover(
+        harness.clients[0].template_world(),
+        SimulationEntity(1)
+    ));
+}
+
+#[test]
+fn host_updates_are_relayed_in_lockstep() {
+    let mut harness = scheme_harness_builder::<LockstepScheme>(1).build();
+
+    let tick = SimulationTick(*harness.server_tick() + 5);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    host_spawn_mover(&mut harness, SimulationEntity(1));
+
+    let tick = SimulationTick(*harness.server_tick() + 5);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    assert!(has_mover(harness.server.world_mut(), SimulationEntity(1)));
+    assert!(has_mover(
+        harness.clients[0].template_world(),
+        SimulationEntity(1)
+    ));
+    harness.assert_template_matches_server(0, tick);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use common::*;
+use nevy::prelude::*;
+use nevy_prediction::{
+    prelude::*,
+    testing::{PredictionTestHarness, simulation_components},
+};
+
+fn lockstep_harness(
+    clients: usize,
+    prediction_interval: Duration,
+) -> PredictionTestHarness<LockstepScheme> {
+    let mut harness = scheme_harness_builder::<LockstepScheme>(clients)
+        .with_client_setup(move |app, _| {
+            app.include_protocol::<(), PredictionMessages>();
+            app.insert_resource(PredictionInterval(prediction_interval));
+        })
+        .build();
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    harness
+}
+
+fn client_set_velocity(harness: &mut PredictionTestHarness<LockstepScheme>, velocity: Velocity) {
+    harness.clients[0]
+        .app
+        .world_mut()
+        .run_system_once(move |mut creator: PredictionUpdateCreator<SetVelocity>| {
+            creator.create(SetVelocity {
+                entity: SimulationEntity(1),
+                velocity,
+            });
+        })
+        .unwrap();
+}
+
+fn velocity(world: &mut World) -> Velocity {
+    simulation_components::<Velocity>(world)[0].1
+}
+
+#[test]
+fn client_updates_are_relayed_to_every_client() {
+    let mut harness = lockstep_harness(2, Duration::from_millis(100));
+
+    client_set_velocity(&mut harness, Velocity(5));
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    assert_eq!(velocity(harness.server.world_mut()), Velocity(5));
+    assert_eq!(velocity(harness.clients[1].template_world()), Velocity(5));
+    harness.assert_template_matches_server(1, tick);
+}
+
+#[test]
+fn client_updates_too_far_ahead_are_rejected() {
+    let mut harness = lockstep_harness(1, Duration::from_millis(500));
+    harness.server.insert_resource(MaxClientUpdateLead(2));
+
+    client_set_velocity(&mut harness, Velocity(5));
+
+    let tick = SimulationTick(*harness.server_tick() + 20);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    assert_eq!(velocity(harness.server.world_mut()), Velocity(0));
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use nevy_prediction::prelude::*;
+
+const MS: Duration = Duration::from_millis(1);
+
+fn receive(link: &LoopbackLink, now: Duration) -> Vec<u32> {
+    link.receive::<u32>(LoopbackSide::Client, now)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn messages_are_delivered_after_latency() {
+    let link = LoopbackLink::new(LoopbackConditions {
+        latency: 100 * MS,
+        ..Default::default()
+    });
+
+    link.send(LoopbackSide::Server, Duration::ZERO, &1u32)
+        .unwrap();
+    link.send(LoopbackSide::Server, 50 * MS, &2u32).unwrap();
+
+    assert_eq!(receive(&link, 99 * MS), Vec::<u32>::new());
+    assert_eq!(receive(&link, 100 * MS), vec![1]);
+    assert_eq!(receive(&link, 149 * MS), Vec::<u32>::new());
+    assert_eq!(receive(&link, 150 * MS), vec![2]);
+
+    // Messages are only delivered to the other side.
+    link.send(LoopbackSide::Server, Duration::ZERO, &3u32)
+        .unwrap();
+    assert!(
+        link.receive::<u32>(LoopbackSide::Server, Duration::from_secs(1))
+            .is_empty()
+    );
+}
+
+#[test]
+fn lost_messages_are_retransmitted_in_order() {
+    let link = LoopbackLink::new(LoopbackConditions {
+        latency: 10 * MS,
+        loss: 1.,
+        retransmit_delay: 100 * MS,
+        ..Default::default()
+    });
+
+    link.send(LoopbackSide::Server, Duration::ZERO, &1u32)
+        .unwrap();
+
+    link.set_conditions(LoopbackConditions {
+        latency: 10 * MS,
+        ..Default::default()
+    });
+
+    link.send(LoopbackSide::Server, Duration::ZERO, &2u32)
+        .unwrap();
+
+    // The second message is held back by the lost one because the link is ordered.
+    assert_eq!(receive(&link, 100 * MS), Vec::<u32>::new());
+    assert_eq!(receive(&link, 110 * MS), vec![1, 2]);
+}
+
+#[test]
+fn reordering_delivers_messages_as_they_arrive() {
+    let link = LoopbackLink::new(LoopbackConditions {
+        latency: 10 * MS,
+        loss: 1.,
+        retransmit_delay: 100 * MS,
+        reordering: true,
+        ..Default::default()
+    });
+
+    link.send(LoopbackSide::Server, Duration::ZERO, &1u32)
+        .unwrap();
+
+    link.set_conditions(LoopbackConditions {
+        latency: 10 * MS,
+        reordering: true,
+        ..Default::default()
+    });
+
+    link.send(LoopbackSide::Server, Duration::ZERO, &2u32)
+        .unwrap();
+
+    assert_eq!(receive(&link, 10 * MS), vec![2]);
+    assert_eq!(receive(&link, 110 * MS), vec![1]);
+}
+
+#[test]
+fn conditions_are_deterministic() {
+    let conditions = LoopbackConditions {
+        latency: 10 * MS,
+        jitter: 50 * MS,
+        loss: 0.3,
+        reordering: true,
+        seed: 7,
+        ..Default::default()
+    };
+
+    let deliveries = || {
+        let link = LoopbackLink::new(conditions.clone());
+
+        for message in 0..100u32 {
+            link.send(LoopbackSide::Server, message * MS, &message)
+                .unwrap();
+        }
+
+        (0..300)
+            .map(|ms| receive(&link, ms * MS))
+            .collect::<Vec<_>>()
+    };
+
+    let first = deliveries();
+
+    assert_eq!(first, deliveries());
+    assert_eq!(first.iter().flatten().count(), 100);
+    assert!(
+        !first.iter().flatten().is_sorted(),
+        "Jitter and loss should reorder some messages"
+    );
+}
+
+#[test]
+fn unread_messages_expire() {
+    let link = LoopbackLink::new(LoopbackConditions::default());
+
+    link.send(LoopbackSide::Server, Duration::ZERO, &1u32)
+        .unwrap();
+    link.send(LoopbackSide::Server, Duration::ZERO, &2u64)
+        .unwrap();
+
+    assert_eq!(receive(&link, Duration::ZERO), vec![1]);
+
+    // Nothing receives the `u64`, so it is discarded once it expires.
+    link.send(
+        LoopbackSide::Server,
+        LoopbackLink::UNREAD_EXPIRY + MS,
+        &3u32,
+    )
+    .unwrap();
+
+    assert!(
+        link.receive::<u64>(LoopbackSide::Client, LoopbackLink::UNREAD_EXPIRY + MS)
+            .is_empty()
+    );
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*, time::TimeUpdateStrategy};
+use common::*;
+use nevy_prediction::{prelude::*, testing::simulation_components};
+
+#[test]
+fn offline_updates_are_applied() {
+    let mut app = App::new();
+
+    app.add_plugins(MinimalPlugins);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        50,
+    )));
+    app.add_plugins(NevyPredictionOfflinePlugin::<TestScheme>::default());
+
+    app.update();
+
+    app.world_mut()
+        .run_system_once(|mut creator: PredictionUpdateCreator<SpawnMover>| {
+            creator.create(SpawnMover {
+                entity: SimulationEntity(1),
+            });
+        })
+        .unwrap();
+
+    for _ in 0..3 {
+        app.update();
+    }
+
+    assert_eq!(
+        simulation_components::<Position>(app.world_mut()),
+        vec![(SimulationEntity(1), Position(0))]
+    );
+}
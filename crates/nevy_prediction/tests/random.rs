@@ -0,0 +1,44 @@
+mod common;
+
+use common::*;
+use nevy_prediction::prelude::*;
+
+#[test]
+fn instances_draw_the_same_random_numbers() {
+    let mut harness = harness(2);
+
+    let tick = SimulationTick(*harness.server_tick() + 20);
+
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    let server_rng = *harness.server.world().resource::<SimulationRng>();
+    assert_eq!(server_rng.seed(), TEST_SEED);
+
+    let server_draws = &harness.server.world().resource::<RandomDraws>().0;
+
+    for client in &mut harness.clients {
+        assert_eq!(
+            client.app.world().resource::<SimulationRng>().seed(),
+            TEST_SEED
+        );
+
+        let template_world = client.template_world();
+
+        assert_eq!(template_world.resource::<SimulationRng>().seed(), TEST_SEED);
+
+        let client_draws = &template_world.resource::<RandomDraws>().0;
+
+        assert!(!client_draws.is_empty());
+
+        for (tick, value) in client_draws {
+            let Some((_, server_value)) = server_draws
+                .iter()
+                .find(|(server_tick, _)| server_tick == tick)
+            else {
+                continue;
+            };
+
+            assert_eq!(value, server_value, "Random numbers diverged on {:?}", tick);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use common::*;
+use nevy_prediction::{
+    common::simulation::checksum::{SimulationChecksums, TickChecksums},
+    prelude::*,
+    testing::PredictionTestHarness,
+};
+
+fn template_checksums(app: &App, tick: SimulationTick) -> Option<TickChecksums> {
+    app.world()
+        .resource::<TemplateWorld>()
+        .resource::<SimulationChecksums>()
+        .get(tick)
+        .cloned()
+}
+
+fn start_recording(harness: &mut PredictionTestHarness<TestScheme>) {
+    harness.clients[0]
+        .app
+        .world_mut()
+        .resource_mut::<SessionRecorder>()
+        .start();
+}
+
+fn stop_recording(harness: &mut PredictionTestHarness<TestScheme>) -> SessionRecording {
+    harness.clients[0]
+        .app
+        .world_mut()
+        .resource_mut::<SessionRecorder>()
+        .stop()
+        .unwrap()
+}
+
+#[test]
+fn session_recordings_replay_the_same_simulation() {
+    let mut harness = harness(1);
+    start_recording(&mut harness);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 5);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    server_update(
+        &mut harness,
+        UpdateComponent {
+            entity: SimulationEntity(1),
+            component: Velocity(3),
+        },
+        false,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    let recording = stop_recording(&mut harness);
+    let expected = template_checksums(&harness.clients[0].app, tick).unwrap();
+
+    let mut replay = client_app::<TestScheme>();
+    replay.insert_resource(SessionReplay::new(recording));
+
+    for _ in 0..200 {
+        replay.update();
+
+        if template_checksums(&replay, tick).is_some() {
+            break;
+        }
+    }
+
+    assert_eq!(
+        template_checksums(&replay, tick),
+        Some(expected),
+        "The replayed template world doesn't match the recorded client at {:?}",
+        tick
+    );
+}
+
+#[test]
+fn session_recordings_started_mid_session_wait_for_a_reset() {
+    let mut harness = harness(1);
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    start_recording(&mut harness);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+    harness.update_frames(10);
+
+    assert!(
+        harness.clients[0]
+            .app
+            .world()
+            .resource::<SessionRecorder>()
+            .is_awaiting_reset()
+    );
+    assert!(stop_recording(&mut harness).entries.is_empty());
+}
+
+#[test]
+fn server_recordings_replay_the_same_simulation() {
+    let mut harness = harness(1);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+    harness.update_frames(5);
+
+    // Started after the mover spawned, so its state has to come from the recording's snapshot.
+    harness
+        .server
+        .world_mut()
+        .resource_mut::<ServerRecorder>()
+        .start();
+    harness.update_frames(2);
+
+    server_update(
+        &mut harness,
+        UpdateComponent {
+            entity: SimulationEntity(1),
+            component: Velocity(2),
+        },
+        false,
+    );
+    harness.update_frames(10);
+
+    let recording = harness
+        .server
+        .world_mut()
+        .resource_mut::<ServerRecorder>()
+        .stop()
+        .unwrap();
+    let end_tick = recording.end_tick;
+    let expected = harness
+        .server
+        .world()
+        .resource::<SimulationChecksums>()
+        .get(end_tick)
+        .cloned()
+        .unwrap();
+
+    let mut replay = App::new();
+    replay.add_plugins(MinimalPlugins);
+    replay.insert_resource(TimeUpdateStrategy::ManualDuration(
+        TestScheme::step_interval(),
+    ));
+    replay.add_plugins(NevyPredictionReplayPlugin::<TestScheme>::default());
+    replay.insert_resource(ServerReplay::new(recording));
+
+    for _ in 0..200 {
+        replay.update();
+
+        let world = replay.world();
+        if world
+            .resource::<ServerReplay>()
+            .is_finished(world.resource::<Time<SimulationTime>>())
+        {
+            break;
+        }
+    }
+
+    assert_eq!(
+        replay
+            .world()
+            .resource::<SimulationChecksums>()
+            .get(end_tick),
+        Some(&expected),
+        "The replayed simulation doesn't match the server at {:?}",
+        end_tick
+    );
+}
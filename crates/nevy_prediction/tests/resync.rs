@@ -0,0 +1,76 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use nevy_prediction::{
+    prelude::*,
+    testing::{PredictionTestHarness, simulation_components},
+};
+
+fn request_resync(
+    harness: &mut PredictionTestHarness<TestScheme>,
+    entities: Vec<SimulationEntity>,
+) {
+    harness.clients[0]
+        .app
+        .world_mut()
+        .write_message(RequestResync {
+            target: ResyncTarget::Entities(entities),
+        });
+}
+
+fn template_has(harness: &mut PredictionTestHarness<TestScheme>, entity: SimulationEntity) -> bool {
+    simulation_components::<Position>(harness.clients[0].template_world())
+        .iter()
+        .any(|&(simulation_entity, _)| simulation_entity == entity)
+}
+
+fn resync_harness(cooldown: u32) -> PredictionTestHarness<TestScheme> {
+    let mut harness = harness(1);
+
+    harness
+        .server
+        .insert_resource(ResyncRequestCooldown(cooldown));
+    harness.clients[0].app.insert_resource(AutoResync(false));
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    harness
+}
+
+#[test]
+fn entity_resyncs_spawn_missing_entities() {
+    let mut harness = resync_harness(0);
+
+    spawn_unsent_mover(&mut harness, SimulationEntity(1));
+    harness.update_frames(5);
+    assert!(!template_has(&mut harness, SimulationEntity(1)));
+
+    request_resync(&mut harness, vec![SimulationEntity(1)]);
+    harness.update_frames(10);
+
+    assert!(
+        template_has(&mut harness, SimulationEntity(1)),
+        "The targeted resync didn't spawn the missing entity"
+    );
+}
+
+#[test]
+fn resync_requests_within_the_cooldown_are_dropped() {
+    let mut harness = resync_harness(1000);
+
+    spawn_unsent_mover(&mut harness, SimulationEntity(1));
+    request_resync(&mut harness, vec![SimulationEntity(1)]);
+    harness.update_frames(10);
+    assert!(template_has(&mut harness, SimulationEntity(1)));
+
+    spawn_unsent_mover(&mut harness, SimulationEntity(2));
+    request_resync(&mut harness, vec![SimulationEntity(2)]);
+    harness.update_frames(10);
+
+    assert!(
+        !template_has(&mut harness, SimulationEntity(2)),
+        "A resync request within the cooldown was accepted"
+    );
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::*;
+use nevy::prelude::*;
+use nevy_prediction::{prelude::*, server::rooms::SimulationRoom, testing::PredictionTestHarness};
+
+#[test]
+fn rooms_follow_the_time_scale() {
+    let mut harness = harness(0);
+
+    let room_entity = harness
+        .server
+        .world_mut()
+        .spawn(SimulationRoom::new::<TestScheme>())
+        .id();
+
+    let room_tick = |harness: &PredictionTestHarness<TestScheme>| {
+        harness
+            .server
+            .world()
+            .get::<SimulationRoom>(room_entity)
+            .unwrap()
+            .current_tick()
+    };
+
+    harness.update_frames(10);
+
+    let running_tick = room_tick(&harness);
+    assert!(*running_tick > 0, "The room didn't advance");
+
+    harness
+        .server
+        .world_mut()
+        .resource_mut::<SimulationTimeScale>()
+        .pause();
+
+    harness.update_frames(10);
+
+    assert_eq!(
+        room_tick(&harness),
+        running_tick,
+        "The room advanced while paused"
+    );
+
+    harness
+        .server
+        .world_mut()
+        .resource_mut::<SimulationTimeScale>()
+        .resume();
+
+    harness.update_frames(10);
+
+    assert!(room_tick(&harness) > running_tick);
+}
+
+#[test]
+fn room_clients_follow_the_time_scale_and_tick_rate() {
+    let mut harness = harness_builder(1)
+        .with_client_setup(|app, _| {
+            app.include_protocol::<(), PredictionMessages>();
+            app.insert_resource(ResumeSessions(false));
+        })
+        .build();
+
+    let room_entity = harness
+        .server
+        .world_mut()
+        .spawn(SimulationRoom::new::<TestScheme>())
+        .id();
+
+    let client_entity = harness.clients[0].client_entity;
+    harness
+        .server
+        .world_mut()
+        .entity_mut(client_entity)
+        .remove::<PredictionClient>()
+        .insert(InRoom(room_entity));
+
+    harness.update_frames(10);
+
+    let running_tick = harness.clients[0].template_tick();
+    assert!(
+        *running_tick > 0,
+        "The room client's template world didn't advance"
+    );
+
+    harness
+        .server
+        .world_mut()
+        .resource_mut::<SimulationTimeScale>()
+        .pause();
+
+    harness.update_frames(3);
+
+    assert!(
+        harness.clients[0]
+            .app
+            .world()
+            .resource::<SimulationTimeScale>()
+            .paused,
+        "The room client wasn't sent the time scale"
+    );
+
+    // Ticks that were already sent can still be executed, so the template world is compared once it has caught up.
+    let paused_tick = harness.clients[0].template_tick();
+    harness.update_frames(10);
+
+    assert_eq!(
+        harness.clients[0].template_tick(),
+        paused_tick,
+        "The room client advanced while paused"
+    );
+
+    harness
+        .server
+        .world_mut()
+        .resource_mut::<SimulationTimeScale>()
+        .resume();
+
+    let interval = Duration::from_millis(100);
+
+    harness
+        .server
+        .world_mut()
+        .write_message(ChangeTickRate { interval });
+
+    harness
+        .run_until(20, |harness| {
+            harness.clients[0]
+                .template_world()
+                .resource::<Time<SimulationTime>>()
+                .tick_rate()
+                .interval
+                == interval
+        })
+        .expect("The room client wasn't sent the tick rate change");
+
+    let room_interval = harness
+        .server
+        .world()
+        .get::<SimulationRoom>(room_entity)
+        .unwrap()
+        .tick_rate()
+        .interval;
+
+    assert_eq!(
+        room_interval, interval,
+        "The room's tick rate wasn't changed"
+    );
+}
@@ -0,0 +1,16 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use nevy::prelude::*;
+use nevy_prediction::prelude::*;
+
+#[test]
+#[should_panic(expected = "to an app twice")]
+fn adding_a_scheme_twice_panics() {
+    let mut app = App::new();
+
+    app.add_plugins((MinimalPlugins, NevyPlugins::default()));
+    app.add_plugins(NevyPredictionServerPlugin::<TestScheme>::default());
+    app.add_plugins(NevyPredictionReplayPlugin::<TestScheme>::default());
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::*;
+use nevy_prediction::{
+    prelude::*, server::session::ResumedSession, testing::PredictionTestHarness,
+};
+
+/// Disconnects the client, runs `while_disconnected` and then reconnects it on a new link.
+///
+/// Returns whether the server resumed the client's session.
+fn reconnect(while_disconnected: impl FnOnce(&mut PredictionTestHarness<TestScheme>)) -> bool {
+    let mut harness = harness(1);
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    disconnect_client(&mut harness, 0);
+
+    harness.update_frames(2);
+
+    while_disconnected(&mut harness);
+
+    harness.update_frames(2);
+
+    connect_client(&mut harness, 0);
+
+    harness.update_frames(10);
+
+    harness
+        .server
+        .world()
+        .entity(harness.clients[0].client_entity)
+        .contains::<ResumedSession>()
+}
+
+#[test]
+fn session_resumes_after_resyncable_changes() {
+    assert!(reconnect(|harness| {
+        server_update(
+            harness,
+            UpdateComponent {
+                entity: SimulationEntity(1),
+                component: Velocity(2),
+            },
+            false,
+        );
+    }));
+}
+
+#[test]
+fn session_resets_after_unresyncable_updates() {
+    assert!(!reconnect(|harness| {
+        server_update(
+            harness,
+            SpawnMover {
+                entity: SimulationEntity(2),
+            },
+            false,
+        );
+    }));
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use nevy::prelude::*;
+use nevy_prediction::{prelude::*, testing::simulation_components};
+
+#[test]
+fn initial_snapshots_are_sent_in_chunks() {
+    let mut harness = harness_builder(1)
+        .with_server_plugin(NevyPredictionServerPlugin::new(Update).with_initial_snapshot())
+        .with_client_setup(|app, _| {
+            app.include_protocol::<(), PredictionMessages>();
+            app.insert_resource(ResumeSessions(false));
+        })
+        .build();
+
+    harness
+        .server
+        .world_mut()
+        .resource_mut::<InitialSnapshotSettings>()
+        .chunk_size = 3;
+
+    let tick = SimulationTick(*harness.server_tick() + 5);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    // The client reconnects to a server that already has entities, which it only learns about from the snapshot.
+    disconnect_client(&mut harness, 0);
+    harness.update_frames(2);
+
+    for entity in 0..10 {
+        spawn_unsent_mover(&mut harness, SimulationEntity(entity));
+    }
+
+    connect_client(&mut harness, 0);
+
+    let client_entity = harness.clients[0].client_entity;
+    let mut snapshot_frames = 0;
+
+    harness
+        .run_until(100, |harness| {
+            let sending = harness
+                .server
+                .world()
+                .entity(client_entity)
+                .contains::<InitialSnapshot>();
+
+            if sending {
+                snapshot_frames += 1;
+            }
+
+            snapshot_frames > 0 && !sending
+        })
+        .unwrap();
+
+    // 10 entities take 4 chunks, and the snapshot is finished in the same frame as the last chunk.
+    assert!(
+        snapshot_frames >= 3,
+        "The snapshot of 10 entities was only in progress for {} frames with chunks of 3",
+        snapshot_frames
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 5);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    let client = &mut harness.clients[0];
+    assert!(
+        client
+            .app
+            .world()
+            .resource::<SnapshotStatus>()
+            .is_complete()
+    );
+    assert_eq!(
+        simulation_components::<Position>(client.template_world()).len(),
+        10
+    );
+    harness.assert_template_matches_server(0, tick);
+}
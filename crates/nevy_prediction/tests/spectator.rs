@@ -0,0 +1,61 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::*;
+use nevy::prelude::*;
+use nevy_prediction::{prelude::*, testing::PredictionTestHarness};
+
+fn main_tick(harness: &PredictionTestHarness<TestScheme>, client: usize) -> SimulationTick {
+    harness.clients[client]
+        .app
+        .world()
+        .resource::<Time<SimulationTime>>()
+        .current_tick()
+}
+
+#[test]
+fn spectators_follow_the_template_world() {
+    let mut harness = harness_builder(2)
+        .with_client_setup(|app, index| {
+            app.include_protocol::<(), PredictionMessages>();
+            app.insert_resource(PredictionInterval(Duration::from_millis(200)));
+
+            if index == 0 {
+                app.insert_resource(Spectator::default());
+            }
+        })
+        .build();
+
+    server_update(
+        &mut harness,
+        SpawnMover {
+            entity: SimulationEntity(1),
+        },
+        false,
+    );
+    server_update(
+        &mut harness,
+        UpdateComponent {
+            entity: SimulationEntity(1),
+            component: Velocity(1),
+        },
+        false,
+    );
+
+    let tick = SimulationTick(*harness.server_tick() + 20);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    assert!(
+        main_tick(&harness, 0) <= harness.clients[0].template_tick(),
+        "The spectator predicted ahead of the template world"
+    );
+    assert!(
+        main_tick(&harness, 1) > harness.clients[1].template_tick(),
+        "The predicting client didn't predict ahead of the template world"
+    );
+
+    // The mover keeps moving, so the spectator's main world only matches the template world if it was extracted from it.
+    harness.assert_prediction_converged::<Position>(0);
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::*;
+use nevy::prelude::*;
+use nevy_prediction::{prelude::*, testing::simulation_components};
+
+fn position(world: &mut World, entity: SimulationEntity) -> Position {
+    simulation_components::<Position>(world)
+        .into_iter()
+        .find(|&(simulation_entity, _)| simulation_entity == entity)
+        .map(|(_, position)| position)
+        .unwrap()
+}
+
+#[test]
+fn only_the_prediction_subset_is_predicted() {
+    let mut harness = harness_builder(1)
+        .with_client_setup(|app, _| {
+            app.include_protocol::<(), PredictionMessages>();
+            app.insert_resource(PredictionInterval(Duration::from_millis(200)));
+            app.world_mut()
+                .resource_mut::<PredictionSubset>()
+                .only([SimulationEntity(1)]);
+        })
+        .build();
+
+    for entity in [SimulationEntity(1), SimulationEntity(2)] {
+        server_update(&mut harness, SpawnMover { entity }, false);
+        server_update(
+            &mut harness,
+            UpdateComponent {
+                entity,
+                component: Velocity(1),
+            },
+            false,
+        );
+    }
+
+    let tick = SimulationTick(*harness.server_tick() + 20);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    let client = &mut harness.clients[0];
+
+    assert!(
+        position(client.app.world_mut(), SimulationEntity(1)).0
+            > position(client.template_world(), SimulationEntity(1)).0,
+        "The entity in the subset wasn't predicted ahead of the template world"
+    );
+    assert_eq!(
+        position(client.app.world_mut(), SimulationEntity(2)),
+        position(client.template_world(), SimulationEntity(2)),
+        "The entity outside the subset was predicted"
+    );
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::*;
+use nevy_prediction::{prelude::*, testing::PredictionTestHarness};
+
+fn set_server_time_scale(
+    harness: &mut PredictionTestHarness<TestScheme>,
+    set: impl FnOnce(&mut SimulationTimeScale),
+) {
+    set(&mut harness
+        .server
+        .world_mut()
+        .resource_mut::<SimulationTimeScale>());
+}
+
+/// The client's estimate of the server's simulation time.
+fn estimated_server_time(harness: &PredictionTestHarness<TestScheme>) -> Duration {
+    let world = harness.clients[0].app.world();
+    let tick_rate = world
+        .resource::<TemplateWorld>()
+        .resource::<Time<SimulationTime>>()
+        .tick_rate();
+
+    world.resource::<ServerTickSamples>().estimated_time(
+        tick_rate,
+        world.resource::<SimulationTimeScale>().effective(),
+        world.resource::<Time<PredictionClock>>().elapsed(),
+    )
+}
+
+#[test]
+fn clients_follow_the_server_time_scale() {
+    let mut harness = harness(1);
+
+    let tick = SimulationTick(*harness.server_tick() + 10);
+    harness.run_until_synchronized(tick, 200).unwrap();
+
+    set_server_time_scale(&mut harness, |time_scale| time_scale.scale = 2.);
+    harness.update_frames(5);
+
+    assert_eq!(
+        harness.clients[0]
+            .app
+            .world()
+            .resource::<SimulationTimeScale>()
+            .scale,
+        2.
+    );
+
+    set_server_time_scale(&mut harness, SimulationTimeScale::pause);
+    harness.update_frames(5);
+
+    assert!(
+        harness.clients[0]
+            .app
+            .world()
+            .resource::<SimulationTimeScale>()
+            .paused,
+        "The client wasn't paused with the server"
+    );
+
+    let paused_time = estimated_server_time(&harness);
+    let paused_tick = harness.clients[0].template_tick();
+    harness.update_frames(10);
+
+    assert_eq!(
+        estimated_server_time(&harness),
+        paused_time,
+        "The estimated server time advanced while the server was paused"
+    );
+    assert_eq!(harness.clients[0].template_tick(), paused_tick);
+
+    set_server_time_scale(&mut harness, SimulationTimeScale::resume);
+    harness.update_frames(10);
+
+    assert!(estimated_server_time(&harness) > paused_time);
+    assert!(harness.clients[0].template_tick() > paused_tick);
+}